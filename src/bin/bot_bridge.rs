@@ -1,19 +1,31 @@
 #[cfg(target_arch = "wasm32")]
 fn main() {}
 
+#[cfg(not(target_arch = "wasm32"))]
+use anyhow::Context;
 #[cfg(not(target_arch = "wasm32"))]
 use clap::Parser;
 #[cfg(not(target_arch = "wasm32"))]
 use futures::{SinkExt, StreamExt};
 #[cfg(not(target_arch = "wasm32"))]
+use std::io::Write;
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::SocketAddr;
+#[cfg(not(target_arch = "wasm32"))]
 use std::path::PathBuf;
 #[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+#[cfg(not(target_arch = "wasm32"))]
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 #[cfg(not(target_arch = "wasm32"))]
 use tokio::net::TcpListener;
 #[cfg(not(target_arch = "wasm32"))]
 use tokio::process::Command;
 #[cfg(not(target_arch = "wasm32"))]
+use tokio::time::{Instant, MissedTickBehavior};
+#[cfg(not(target_arch = "wasm32"))]
 use tokio_tungstenite::{accept_async, tungstenite::Message};
 
 /// Bridge between the browser and cold-clear-2 via TBP over stdin/stdout.
@@ -29,12 +41,70 @@ struct Opts {
     /// Optional path to bot config JSON passed to cold-clear-2
     #[arg(long)]
     bot_config: Option<PathBuf>,
+    /// Seconds between keepalive pings sent to the browser (0 disables)
+    #[arg(long, default_value_t = 20)]
+    ping_interval: u64,
+    /// Seconds to wait for a pong before dropping the connection and the bot
+    #[arg(long, default_value_t = 10)]
+    pong_timeout: u64,
+    /// Append every forwarded TBP line (with direction and timestamp) to this file
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+    /// Seconds to wait for the bot's TBP `info` message before giving up
+    #[arg(long, default_value_t = 10)]
+    info_timeout: u64,
+}
+
+/// Handle to a background writer that appends forwarded traffic to the log file.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone)]
+struct TrafficLog {
+    tx: mpsc::Sender<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TrafficLog {
+    fn open(path: &PathBuf) -> anyhow::Result<Self> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open log file {}", path.display()))?;
+        let (tx, rx) = mpsc::channel::<String>();
+        // Writes happen on their own thread so a slow disk never stalls forwarding.
+        std::thread::spawn(move || {
+            for line in rx {
+                if writeln!(file, "{}", line)
+                    .and_then(|_| file.flush())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        Ok(Self { tx })
+    }
+
+    fn record(&self, addr: SocketAddr, direction: &str, line: &str) {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let _ = self.tx.send(format!(
+            "{}.{:03} {} {} {}",
+            ts.as_secs(),
+            ts.subsec_millis(),
+            addr,
+            direction,
+            line
+        ));
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let opts = Opts::parse();
+    let log = opts.log_file.as_ref().map(TrafficLog::open).transpose()?;
     let listener = TcpListener::bind(&opts.listen).await?;
     println!("Bot bridge listening on ws://{}", opts.listen);
 
@@ -42,8 +112,9 @@ async fn main() -> anyhow::Result<()> {
         let (stream, addr) = listener.accept().await?;
         println!("WS connected: {}", addr);
         let opts = opts.clone();
+        let log = log.clone();
         tokio::spawn(async move {
-            if let Err(e) = handle_conn(stream, opts).await {
+            if let Err(e) = handle_conn(stream, addr, opts, log).await {
                 eprintln!("connection error {}: {:?}", addr, e);
             }
         });
@@ -51,58 +122,140 @@ async fn main() -> anyhow::Result<()> {
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-async fn handle_conn(stream: tokio::net::TcpStream, opts: Opts) -> anyhow::Result<()> {
+async fn handle_conn(
+    stream: tokio::net::TcpStream,
+    addr: SocketAddr,
+    opts: Opts,
+    log: Option<TrafficLog>,
+) -> anyhow::Result<()> {
     let ws_stream = accept_async(stream).await?;
     let (mut ws_tx, mut ws_rx) = ws_stream.split();
 
-    // Spawn cold-clear-2
-    let mut cmd = Command::new(&opts.bot_path);
-    if let Some(cfg) = opts.bot_config.as_ref() {
-        cmd.arg("--config").arg(cfg);
-    }
-    let mut child = cmd.stdin(std::process::Stdio::piped()).stdout(std::process::Stdio::piped()).spawn()?;
-    let mut bot_stdin = child
-        .stdin
-        .take()
-        .ok_or_else(|| anyhow::anyhow!("failed to open bot stdin"))?;
-    let bot_stdout = child
-        .stdout
-        .take()
-        .ok_or_else(|| anyhow::anyhow!("failed to open bot stdout"))?;
-
-    let mut bot_reader = BufReader::new(bot_stdout).lines();
-    let (bot_tx, mut bot_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
-
-    tokio::spawn(async move {
-        while let Ok(Some(line)) = bot_reader.next_line().await {
-            if bot_tx.send(line).is_err() {
-                break;
-            }
+    let result: anyhow::Result<()> = async {
+        // Spawn cold-clear-2
+        let mut cmd = Command::new(&opts.bot_path);
+        if let Some(cfg) = opts.bot_config.as_ref() {
+            cmd.arg("--config").arg(cfg);
         }
-    });
+        let mut child = cmd
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("bot failed to start ({})", opts.bot_path.display()))?;
+        let mut bot_stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("failed to open bot stdin"))?;
+        let bot_stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("failed to open bot stdout"))?;
 
-    // Forward ws <-> bot
-    loop {
-        tokio::select! {
-            Some(line) = bot_rx.recv() => {
-                ws_tx.send(Message::Text(line)).await?;
+        let mut bot_reader = BufReader::new(bot_stdout).lines();
+        let (bot_tx, mut bot_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+
+        tokio::spawn(async move {
+            while let Ok(Some(line)) = bot_reader.next_line().await {
+                if bot_tx.send(line).is_err() {
+                    break;
+                }
             }
-            Some(msg) = ws_rx.next() => {
-                match msg {
-                    Ok(Message::Text(t)) => {
-                        bot_stdin.write_all(t.as_bytes()).await?;
-                        bot_stdin.write_all(b"\n").await?;
+        });
+
+        // Keepalive: ping on an interval and give up if a pong doesn't arrive in time.
+        let ping_every = Duration::from_secs(opts.ping_interval.max(1));
+        let mut ping_timer = tokio::time::interval_at(Instant::now() + ping_every, ping_every);
+        ping_timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut pong_deadline: Option<Instant> = None;
+
+        // TBP bots announce themselves with `info` first; browser messages wait until then so
+        // a `start` never reaches a bot that hasn't identified itself.
+        let mut info_deadline = Some(Instant::now() + Duration::from_secs(opts.info_timeout));
+        let mut held: Vec<String> = Vec::new();
+
+        // Forward ws <-> bot
+        loop {
+            tokio::select! {
+                Some(line) = bot_rx.recv() => {
+                    if let Some(log) = &log {
+                        log.record(addr, "bot->ws", &line);
                     }
-                    Ok(Message::Close(_)) => break,
-                    Ok(Message::Binary(_)) => {}
-                    _ => {}
+                    let info = info_deadline
+                        .is_some()
+                        .then(|| serde_json::from_str::<serde_json::Value>(&line).ok())
+                        .flatten()
+                        .filter(|msg| msg["type"] == "info");
+                    ws_tx.send(Message::Text(line)).await?;
+                    if let Some(info) = info {
+                        info_deadline = None;
+                        println!(
+                            "Bot info ({}): {} {} by {}, features {}",
+                            addr, info["name"], info["version"], info["author"], info["features"]
+                        );
+                        let frame = serde_json::json!({
+                            "type": "bot_info",
+                            "name": info["name"],
+                            "version": info["version"],
+                            "author": info["author"],
+                            "features": info["features"],
+                        });
+                        ws_tx.send(Message::Text(frame.to_string())).await?;
+                        for t in held.drain(..) {
+                            bot_stdin.write_all(t.as_bytes()).await.context("bot stdin closed")?;
+                            bot_stdin.write_all(b"\n").await.context("bot stdin closed")?;
+                        }
+                    }
+                }
+                _ = tokio::time::sleep_until(info_deadline.unwrap_or_else(Instant::now)), if info_deadline.is_some() => {
+                    anyhow::bail!("bot sent no TBP info within {}s (wrong --bot-path?)", opts.info_timeout);
+                }
+                _ = ping_timer.tick(), if opts.ping_interval > 0 => {
+                    ws_tx.send(Message::Ping(Vec::new())).await?;
+                    if pong_deadline.is_none() {
+                        pong_deadline = Some(Instant::now() + Duration::from_secs(opts.pong_timeout));
+                    }
+                }
+                _ = tokio::time::sleep_until(pong_deadline.unwrap_or_else(Instant::now)), if pong_deadline.is_some() => {
+                    anyhow::bail!("no pong from client within {}s", opts.pong_timeout);
                 }
+                Some(msg) = ws_rx.next() => {
+                    match msg {
+                        Ok(Message::Text(t)) => {
+                            if let Some(log) = &log {
+                                log.record(addr, "ws->bot", &t);
+                            }
+                            if info_deadline.is_some() {
+                                held.push(t);
+                                continue;
+                            }
+                            bot_stdin
+                                .write_all(t.as_bytes())
+                                .await
+                                .context("bot stdin closed")?;
+                            bot_stdin.write_all(b"\n").await.context("bot stdin closed")?;
+                        }
+                        Ok(Message::Pong(_)) => pong_deadline = None,
+                        Ok(Message::Close(_)) => break,
+                        Ok(Message::Binary(_)) => {}
+                        _ => {}
+                    }
+                }
+                else => break,
             }
-            else => break,
         }
+
+        let _ = bot_stdin.shutdown().await;
+        let _ = child.kill().await;
+        Ok(())
     }
+    .await;
 
-    let _ = bot_stdin.shutdown().await;
-    let _ = child.kill().await;
-    Ok(())
+    // Tell the browser why the bridge is closing instead of just dropping the socket.
+    if let Err(e) = &result {
+        let frame = serde_json::json!({ "type": "error", "message": format!("{:#}", e) });
+        let _ = ws_tx.send(Message::Text(frame.to_string())).await;
+        let _ = ws_tx.send(Message::Close(None)).await;
+    }
+    result
 }
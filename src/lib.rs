@@ -1,10 +1,12 @@
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
 use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::{from_value, to_value};
 use wasm_bindgen::prelude::*;
-use web_sys::console;
 use tbp::{data as tbp_data, frontend_msg, randomizer as tbp_randomizer, MaybeUnknown};
 
 const WIDTH: usize = 10;
@@ -12,14 +14,30 @@ const VISIBLE_HEIGHT: usize = 20; // Jstris-style visible field
 const BUFFER_HEIGHT: usize = 20; // single-row, non-colliding buffer
 const TOTAL_HEIGHT: usize = VISIBLE_HEIGHT + BUFFER_HEIGHT;
 const LOCK_DELAY_MS: f32 = 500.0;
+const MOVE_RESETS: u8 = 15;
+const MAX_PIECE_BLOCKS: usize = 5;
 
 #[wasm_bindgen(start)]
 pub fn bootstrap() {
     console_error_panic_hook::set_once();
 }
 
+thread_local! {
+    // Every random draw (pieces, garbage holes) goes through here, so a seeded headless run
+    // can swap in its own generator and replay exactly.
+    static GAME_RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    GAME_RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
 fn log(msg: &str) {
-    console::log_1(&JsValue::from_str(msg));
+    #[cfg(target_arch = "wasm32")]
+    web_sys::console::log_1(&JsValue::from_str(msg));
+    // Headless runs (`simulate_bot`) have no console to log to.
+    #[cfg(not(target_arch = "wasm32"))]
+    let _ = msg;
 }
 
 #[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -46,6 +64,10 @@ impl Tetromino {
         ]
     }
 
+    fn from_color_id(id: u8) -> Option<Tetromino> {
+        Tetromino::all().into_iter().find(|p| p.color_id() == id)
+    }
+
     fn color_id(self) -> u8 {
         match self {
             Tetromino::I => 1,
@@ -138,15 +160,6 @@ impl Rotation {
             Rotation::Right => Rotation::Spawn,
         }
     }
-
-    fn rotate_180(self) -> Rotation {
-        match self {
-            Rotation::Spawn => Rotation::Reverse,
-            Rotation::Reverse => Rotation::Spawn,
-            Rotation::Right => Rotation::Left,
-            Rotation::Left => Rotation::Right,
-        }
-    }
 }
 
 #[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -155,13 +168,297 @@ pub struct Point {
     pub y: i8,
 }
 
+/// A sandbox piece for the `CustomPieces` randomizer. Blocks are offsets from the rotation
+/// center in spawn orientation; every rotation tries `(0, 0)` and then `kicks` in order.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct PieceShape {
+    pub blocks: Vec<Point>,
+    pub color: u8,
+    #[serde(default)]
+    pub kicks: Vec<(i32, i32)>,
+}
+
+/// Cells of a piece: four for tetrominoes, up to `MAX_PIECE_BLOCKS` for custom shapes.
+#[derive(Clone, Copy)]
+struct Blocks {
+    cells: [Point; MAX_PIECE_BLOCKS],
+    len: usize,
+}
+
+impl From<[Point; 4]> for Blocks {
+    fn from(blocks: [Point; 4]) -> Self {
+        let mut cells = [Point { x: 0, y: 0 }; MAX_PIECE_BLOCKS];
+        cells[..4].copy_from_slice(&blocks);
+        Self { cells, len: 4 }
+    }
+}
+
+impl std::ops::Deref for Blocks {
+    type Target = [Point];
+
+    fn deref(&self) -> &[Point] {
+        &self.cells[..self.len]
+    }
+}
+
+impl IntoIterator for Blocks {
+    type Item = Point;
+    type IntoIter = std::iter::Take<std::array::IntoIter<Point, MAX_PIECE_BLOCKS>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.cells.into_iter().take(self.len)
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct GameSettings {
     pub das: u32,
     pub arr: u32,
+    pub arr_mode: ArrMode,
     pub soft_drop: SoftDropSpeed,
+    /// `false` hides the ghost whatever `ghost_style` says (the older switch).
     pub ghost_enabled: bool,
+    pub ghost_style: GhostStyle,
     pub grid: GridStyle,
+    pub clear_gravity: ClearGravity,
+    /// Pieces kept queued ahead of the active piece (what bots see via TBP). Randomizers
+    /// that read the board deal by board state at draw time, so they need the default depth.
+    pub lookahead_depth: usize,
+    /// Pieces shown in the frame's next preview; never more than `lookahead_depth`.
+    pub preview_count: usize,
+    pub garbage: GarbageSettings,
+    /// Flip the playfield horizontally; human left/right and CW/CCW are swapped to match.
+    pub mirror: bool,
+    pub combo: ComboSettings,
+    /// Holding soft drop on the floor locks immediately rather than after the lock delay.
+    pub soft_drop_lock: bool,
+    /// Holding soft drop on the floor this long locks, ahead of the lock delay. Off when
+    /// unset; `soft_drop_lock` still wins when both are on.
+    pub soft_drop_lock_ms: Option<f32>,
+    /// Clockwise rotation (0, 90, 180 or 270) applied to the frame's field, active, ghost
+    /// and preview blocks. At 90/270 the field is VISIBLE_HEIGHT wide and WIDTH tall.
+    pub display_rotation: u16,
+    /// Off, rotations only try the unkicked position and fail against walls and the stack.
+    pub kicks_enabled: bool,
+    pub lock_out_rule: LockOutRule,
+    /// Rows gained by soft drop count as a move for lock resets, so soft dropping onto the
+    /// stack restarts the lock delay (spending a move reset). Off by default.
+    pub soft_drop_resets_lock: bool,
+    pub attack_mode: AttackMode,
+    /// Fraction of the visible field a stack may reach before a `DangerZone` event fires.
+    pub danger_threshold: f32,
+    /// Opening pieces dealt to both players before their randomizers take over.
+    pub initial_queue: Option<Vec<Tetromino>>,
+    /// Report every lock's spin classifier inputs as a `SpinDebug` event.
+    pub spin_debug: bool,
+    /// Off for solo modes: attack is still counted in stats but never sent, and pending
+    /// garbage (injected or scripted included) never lands.
+    pub versus_enabled: bool,
+    /// Record per-tick gravity and soft drop rows for `fallDebug`.
+    pub fall_debug: bool,
+    /// Under `SevenBag`, raise a `DroughtWarning` when a piece provably can't spawn for at
+    /// least this many pieces. Off when unset.
+    pub drought_warning_pieces: Option<u32>,
+    pub hold_top_out: HoldTopOutRule,
+    /// A piece can't lock until this long after it spawned, however short its lock delay
+    /// (soft drop lock, spent resets), so a piece landing at once at 20G can still move.
+    pub spawn_lock_grace_ms: f32,
+    /// When non-zero, a human's hard drop lands the piece and locks after this long instead
+    /// of at once; a second hard drop locks immediately. 0 locks on the first press.
+    pub hard_drop_grace_ms: f32,
+    pub soft_drop_mode: SoftDropMode,
+    /// Report each lock's presses with a `PieceLocked` event, for finesse analysis.
+    pub record_inputs: bool,
+    /// Pre-game countdown: held directions charge DAS and held rotate/hold buttons apply
+    /// on the first frame, but nothing falls or locks until it runs out.
+    pub countdown_ms: f32,
+    /// Total time a piece may spend on the ground, across lock delay resets, before it
+    /// locks whatever the player does.
+    pub max_lock_time_ms: f32,
+}
+
+/// Base attack for a clear, before combo, back-to-back and perfect clear bonuses.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub enum AttackMode {
+    /// Look the clear up in the attack table.
+    #[default]
+    Table,
+    /// `lines_cleared * per_line + offset`, floored at 0, then multiplied by
+    /// `t_spin_multiplier` for T-spins. `{ per_line: 1, offset: -1 }` is "lines minus one".
+    PerLine {
+        per_line: u32,
+        offset: i32,
+        t_spin_multiplier: u32,
+    },
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub enum LockOutRule {
+    /// The human player tops out when any locked cell is outside the visible field; the
+    /// opponent only on a blocked spawn.
+    #[default]
+    Lenient,
+    /// Guideline lock out: either player tops out when a piece locks entirely above the
+    /// visible field.
+    Strict,
+}
+
+/// What a hold does when the piece it would bring in can't spawn.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub enum HoldTopOutRule {
+    /// Refuse the hold and keep the current piece.
+    #[default]
+    Forbid,
+    /// Hold anyway and top out (block out).
+    TopOut,
+}
+
+/// How the combo counter is presented. Attack lookups always use the internal counter.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ComboSettings {
+    /// Count the first clear of a chain as combo 1 instead of combo 0.
+    pub first_clear_counts: bool,
+    /// Added to the displayed value while a combo is running.
+    pub display_offset: u32,
+    /// Combo attack stops growing past this many consecutive clears (counted from 1 for the
+    /// first clear). Unlimited when unset.
+    pub max_combo_for_attack: Option<u32>,
+}
+
+impl ComboSettings {
+    fn display(&self, combo: u32) -> u32 {
+        if combo == 0 {
+            return 0;
+        }
+        let shown = if self.first_clear_counts { combo } else { combo - 1 };
+        shown + self.display_offset
+    }
+}
+
+/// Deserialize settings, rejecting unknown keys and naming the field that failed to parse.
+fn parse_settings_strict(input: serde_json::Value) -> Result<GameSettings, String> {
+    let known = serde_json::to_value(GameSettings::default()).map_err(|e| e.to_string())?;
+    if let Some(path) = unknown_key(&input, &known, "settings") {
+        return Err(format!("unknown field {}", path));
+    }
+    serde_json::from_value(input.clone()).map_err(|e| {
+        // Swap fields into the defaults one at a time to find which one is malformed.
+        let bad = input.as_object().and_then(|fields| {
+            fields.iter().find_map(|(key, value)| {
+                let mut probe = known.clone();
+                probe[key] = value.clone();
+                serde_json::from_value::<GameSettings>(probe).is_err().then_some(key)
+            })
+        });
+        match bad {
+            Some(key) => format!("settings.{}: {}", key, e),
+            None => format!("settings: {}", e),
+        }
+    })
+}
+
+fn unknown_key(input: &serde_json::Value, known: &serde_json::Value, path: &str) -> Option<String> {
+    let (serde_json::Value::Object(input), serde_json::Value::Object(known)) = (input, known) else {
+        return None;
+    };
+    input.iter().find_map(|(key, value)| {
+        let path = format!("{}.{}", path, key);
+        match known.get(key) {
+            None => Some(path),
+            Some(expected) => unknown_key(value, expected, &path),
+        }
+    })
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct GarbageSettings {
+    /// Always put the garbage hole in this column instead of picking one at random.
+    pub fixed_hole: Option<usize>,
+    /// Milliseconds between rows when landing garbage rises gradually; 0 applies it at once.
+    pub rise_interval_ms: f32,
+    /// Holding while garbage is pending cancels `defensive_hold_lines` of it.
+    pub defensive_hold: bool,
+    pub defensive_hold_lines: u32,
+    /// No-cancel rules: attack is always sent in full and incoming garbage still lands,
+    /// instead of attack cancelling pending garbage first.
+    pub send_then_cancel: bool,
+    /// Land garbage from a combo break only after the new piece has had one frame, so it
+    /// rises under the piece instead of landing on it at spawn.
+    pub spawn_safe_frame: bool,
+}
+
+impl Default for GarbageSettings {
+    fn default() -> Self {
+        Self {
+            fixed_hole: None,
+            rise_interval_ms: 0.0,
+            defensive_hold: false,
+            defensive_hold_lines: 1,
+            send_then_cancel: false,
+            spawn_safe_frame: false,
+        }
+    }
+}
+
+impl GameSettings {
+    fn effective_ghost_style(&self) -> GhostStyle {
+        if self.ghost_enabled {
+            self.ghost_style
+        } else {
+            GhostStyle::None
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.preview_count > self.lookahead_depth {
+            return Err(format!(
+                "preview_count {} can't exceed lookahead_depth {}",
+                self.preview_count, self.lookahead_depth
+            ));
+        }
+        if let Some(col) = self.garbage.fixed_hole
+            && col >= WIDTH
+        {
+            return Err(format!("garbage.fixed_hole {} out of range (0..{})", col, WIDTH));
+        }
+        if ![0, 90, 180, 270].contains(&self.display_rotation) {
+            return Err(format!("display_rotation {} must be 0, 90, 180 or 270", self.display_rotation));
+        }
+        if !(self.danger_threshold > 0.0 && self.danger_threshold <= 1.0) {
+            return Err(format!("danger_threshold {} must be in (0, 1]", self.danger_threshold));
+        }
+        if let Some(queue) = &self.initial_queue
+            && queue.is_empty()
+        {
+            return Err("initial_queue must list at least one piece (or be null)".into());
+        }
+        if !(self.spawn_lock_grace_ms.is_finite() && self.spawn_lock_grace_ms >= 0.0) {
+            return Err(format!("spawn_lock_grace_ms {} must be 0 or more", self.spawn_lock_grace_ms));
+        }
+        if !(self.hard_drop_grace_ms.is_finite() && self.hard_drop_grace_ms >= 0.0) {
+            return Err(format!("hard_drop_grace_ms {} must be 0 or more", self.hard_drop_grace_ms));
+        }
+        if let Some(ms) = self.soft_drop_lock_ms
+            && !(ms.is_finite() && ms >= 0.0)
+        {
+            return Err(format!("soft_drop_lock_ms {} must be 0 or more", ms));
+        }
+        if !(self.countdown_ms.is_finite() && self.countdown_ms >= 0.0) {
+            return Err(format!("countdown_ms {} must be 0 or more", self.countdown_ms));
+        }
+        if !(self.max_lock_time_ms.is_finite() && self.max_lock_time_ms > 0.0) {
+            return Err(format!("max_lock_time_ms {} must be positive", self.max_lock_time_ms));
+        }
+        if let SoftDropMode::Absolute { ms_per_row } = self.soft_drop_mode
+            && !(ms_per_row.is_finite() && ms_per_row > 0.0)
+        {
+            return Err(format!("soft drop ms_per_row {} must be positive", ms_per_row));
+        }
+        Ok(())
+    }
 }
 
 impl Default for GameSettings {
@@ -169,9 +466,38 @@ impl Default for GameSettings {
         Self {
             das: 133,
             arr: 10,
+            arr_mode: ArrMode::Auto,
             soft_drop: SoftDropSpeed::Medium,
             ghost_enabled: true,
+            ghost_style: GhostStyle::Full,
             grid: GridStyle::Standard,
+            clear_gravity: ClearGravity::Naive,
+            lookahead_depth: DEFAULT_LOOKAHEAD_DEPTH,
+            preview_count: 6,
+            garbage: GarbageSettings::default(),
+            mirror: false,
+            combo: ComboSettings::default(),
+            soft_drop_lock: false,
+            soft_drop_lock_ms: None,
+            display_rotation: 0,
+            kicks_enabled: true,
+            lock_out_rule: LockOutRule::Lenient,
+            soft_drop_resets_lock: false,
+            attack_mode: AttackMode::Table,
+            danger_threshold: 0.75,
+            initial_queue: None,
+            spin_debug: false,
+            versus_enabled: true,
+            fall_debug: false,
+            drought_warning_pieces: None,
+            hold_top_out: HoldTopOutRule::Forbid,
+            spawn_lock_grace_ms: 0.0,
+            hard_drop_grace_ms: 0.0,
+            soft_drop_mode: SoftDropMode::Multiplier,
+            record_inputs: false,
+            countdown_ms: 0.0,
+            // Enough for every move reset to run a full lock delay.
+            max_lock_time_ms: (MOVE_RESETS as f32 + 1.0) * LOCK_DELAY_MS,
         }
     }
 }
@@ -197,6 +523,36 @@ impl SoftDropSpeed {
     }
 }
 
+/// How a held direction auto-repeats once DAS has charged.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub enum ArrMode {
+    /// `Instant` when `arr` is 0, otherwise `Stepped`.
+    #[default]
+    Auto,
+    /// One cell every `arr` ms (at least 1 ms), so a low ARR still takes a few frames.
+    Stepped,
+    /// Straight to the wall, whatever `arr` is.
+    Instant,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub enum SoftDropMode {
+    /// Soft drop falls `soft_drop.factor()` times faster than current gravity.
+    #[default]
+    Multiplier,
+    /// Soft drop falls a row every `ms_per_row` whatever the gravity, never slower than it.
+    Absolute { ms_per_row: f32 },
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub enum GhostStyle {
+    #[default]
+    Full,
+    /// Only the lowest ghost cell in each column.
+    Outline,
+    None,
+}
+
 #[derive(Clone, Copy, Serialize, Deserialize, Debug)]
 pub enum GridStyle {
     None,
@@ -206,39 +562,155 @@ pub enum GridStyle {
     Full,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub enum ClearGravity {
+    /// Rows above a clear shift down uniformly.
+    #[default]
+    Naive,
+    /// Disconnected groups fall independently after a clear and can chain more clears.
+    Cascade,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
 pub enum RandomizerKind {
     TrueRandom,
+    #[default]
     SevenBag,
     FiveBag,
     SinglePiece { piece: Tetromino },
     LoveTris,
     LoveTrisNoBag,
     LoveTrisOriginal,
+    /// Sandbox: up to seven custom shapes dealt from a bag, standing in for the tetromino
+    /// slots in order (the first shape plays as I, the second as J, ...).
+    CustomPieces { shapes: Vec<PieceShape> },
 }
 
-impl Default for RandomizerKind {
-    fn default() -> Self {
-        RandomizerKind::SevenBag
+impl RandomizerKind {
+    fn validate(&self) -> Result<(), String> {
+        let RandomizerKind::CustomPieces { shapes } = self else {
+            return Ok(());
+        };
+        if shapes.is_empty() || shapes.len() > Tetromino::all().len() {
+            return Err(format!("custom pieces need 1 to 7 shapes, got {}", shapes.len()));
+        }
+        for (i, shape) in shapes.iter().enumerate() {
+            if shape.blocks.is_empty() || shape.blocks.len() > MAX_PIECE_BLOCKS {
+                return Err(format!(
+                    "custom piece {} needs 1 to {} blocks, got {}",
+                    i,
+                    MAX_PIECE_BLOCKS,
+                    shape.blocks.len()
+                ));
+            }
+            if !(1..=8).contains(&shape.color) {
+                return Err(format!("custom piece {} has invalid color id {}", i, shape.color));
+            }
+        }
+        Ok(())
+    }
+
+    // LoveTris variants pick from the board as it is when a piece is drawn.
+    fn reads_board(&self) -> bool {
+        matches!(self, RandomizerKind::LoveTris | RandomizerKind::LoveTrisOriginal)
+    }
+
+    /// A different queue depth draws pieces at different times, so a board-reading kind
+    /// would deal a different sequence; only the default depth is allowed for those.
+    fn check_lookahead(&self, depth: usize) -> Result<(), String> {
+        if self.reads_board() && depth != DEFAULT_LOOKAHEAD_DEPTH {
+            return Err(format!(
+                "{:?} reads the board when dealing, so lookahead_depth must be {}",
+                self, DEFAULT_LOOKAHEAD_DEPTH
+            ));
+        }
+        Ok(())
+    }
+
+    /// One of each variant, in dropdown order, with placeholder parameters.
+    fn catalog() -> Vec<RandomizerKind> {
+        vec![
+            RandomizerKind::SevenBag,
+            RandomizerKind::FiveBag,
+            RandomizerKind::LoveTrisNoBag,
+            RandomizerKind::LoveTrisOriginal,
+            RandomizerKind::TrueRandom,
+            RandomizerKind::LoveTris,
+            RandomizerKind::SinglePiece { piece: Tetromino::I },
+            RandomizerKind::CustomPieces { shapes: Vec::new() },
+        ]
+    }
+
+    // Exhaustive so a new variant can't be added without describing it.
+    fn info(&self) -> RandomizerInfo {
+        let (name, params, bag_state, description): (_, &[_], _, _) = match self {
+            RandomizerKind::TrueRandom => ("TrueRandom", &[], false, "Every piece uniformly at random"),
+            RandomizerKind::SevenBag => ("SevenBag", &[], true, "Shuffled bags of all seven pieces"),
+            RandomizerKind::FiveBag => ("FiveBag", &[], true, "Shuffled bags without S and Z"),
+            RandomizerKind::SinglePiece { .. } => ("SinglePiece", &["piece"], false, "The same piece every time"),
+            RandomizerKind::LoveTris => ("LoveTris", &[], true, "Board-aware picks drawn from a 7 bag"),
+            RandomizerKind::LoveTrisNoBag => ("LoveTrisNoBag", &[], false, "Alternates T and I, ignoring the board"),
+            RandomizerKind::LoveTrisOriginal => ("LoveTrisOriginal", &[], false, "Picks the piece that keeps the stack lowest"),
+            RandomizerKind::CustomPieces { .. } => {
+                ("CustomPieces", &["shapes"], true, "Up to seven custom shapes dealt from a bag")
+            }
+        };
+        RandomizerInfo {
+            name,
+            params,
+            bag_state,
+            description,
+        }
     }
 }
 
-trait Randomizer: std::any::Any {
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RandomizerInfo {
+    name: &'static str,
+    params: &'static [&'static str],
+    bag_state: bool,
+    description: &'static str,
+}
+
+fn randomizer_kinds() -> Vec<RandomizerInfo> {
+    RandomizerKind::catalog().iter().map(RandomizerKind::info).collect()
+}
+
+trait Randomizer: std::any::Any + RandomizerClone {
     fn next(&mut self, board: &Board) -> Tetromino;
     fn bag_state(&self) -> Option<Vec<Tetromino>> {
         None
     }
 }
 
+// Lets a boxed randomizer be copied, bag and all, for checkpoints.
+trait RandomizerClone {
+    fn clone_box(&self) -> Box<dyn Randomizer>;
+}
+
+impl<T: Randomizer + Clone> RandomizerClone for T {
+    fn clone_box(&self) -> Box<dyn Randomizer> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Randomizer> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+#[derive(Clone)]
 struct TrueRandom;
 
 impl Randomizer for TrueRandom {
     fn next(&mut self, _board: &Board) -> Tetromino {
-        let mut rng = thread_rng();
-        *Tetromino::all().choose(&mut rng).unwrap()
+        with_rng(|rng| *Tetromino::all().choose(rng).unwrap())
     }
 }
 
+#[derive(Clone)]
 struct SinglePiece {
     piece: Tetromino,
 }
@@ -249,6 +721,7 @@ impl Randomizer for SinglePiece {
     }
 }
 
+#[derive(Clone)]
 struct SevenBag {
     bag: Vec<Tetromino>,
 }
@@ -260,7 +733,7 @@ impl SevenBag {
 
     fn refill(&mut self) {
         self.bag = Tetromino::all().to_vec();
-        self.bag.shuffle(&mut thread_rng());
+        with_rng(|rng| self.bag.shuffle(rng));
     }
 }
 
@@ -277,6 +750,7 @@ impl Randomizer for SevenBag {
     }
 }
 
+#[derive(Clone)]
 struct FiveBag {
     bag: Vec<Tetromino>,
 }
@@ -292,7 +766,7 @@ impl FiveBag {
             .copied()
             .filter(|p| *p != Tetromino::S && *p != Tetromino::Z)
             .collect();
-        self.bag.shuffle(&mut thread_rng());
+        with_rng(|rng| self.bag.shuffle(rng));
     }
 }
 
@@ -309,6 +783,27 @@ impl Randomizer for FiveBag {
     }
 }
 
+#[derive(Clone)]
+struct CustomPieces {
+    slots: usize,
+    bag: Vec<Tetromino>,
+}
+
+impl Randomizer for CustomPieces {
+    fn next(&mut self, _board: &Board) -> Tetromino {
+        if self.bag.is_empty() {
+            self.bag = Tetromino::all()[..self.slots].to_vec();
+            with_rng(|rng| self.bag.shuffle(rng));
+        }
+        self.bag.pop().unwrap()
+    }
+
+    fn bag_state(&self) -> Option<Vec<Tetromino>> {
+        Some(self.bag.clone())
+    }
+}
+
+#[derive(Clone)]
 struct LoveTris {
     bag: SevenBag,
 }
@@ -330,7 +825,7 @@ impl LoveTris {
         ] {
             let shape = shape_blocks(piece, rot);
             for x in -2..WIDTH as i32 + 2 {
-                if let Some(h) = board.lowest_drop_height(x, &shape) {
+                if let Some(h) = board.lowest_visible_drop_height(x, &shape) {
                     let mut simulated = board.clone();
                     simulated.lock_piece(x, h, &shape, piece.color_id());
                     let lines = simulated.clear_lines();
@@ -352,15 +847,18 @@ impl Randomizer for LoveTris {
         if self.bag.bag.is_empty() {
             self.bag.refill();
         }
-        let mut best_index = 0;
-        let mut best_score = i32::MIN;
-        for (idx, piece) in self.bag.bag.iter().enumerate() {
-            let score = Self::score_candidate(board, *piece);
-            if score > best_score {
-                best_index = idx;
-                best_score = score;
-            }
-        }
+        // Ties go to the piece listed first in `Tetromino::all`, not to the shuffled bag
+        // order, so the pick only depends on the board and the bag's contents.
+        let best_index = self
+            .bag
+            .bag
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, piece)| {
+                (Self::score_candidate(board, **piece), std::cmp::Reverse(piece.color_id()))
+            })
+            .map(|(idx, _)| idx)
+            .unwrap_or(0);
         self.bag.bag.remove(best_index)
     }
 
@@ -369,6 +867,7 @@ impl Randomizer for LoveTris {
     }
 }
 
+#[derive(Clone)]
 struct LoveTrisNoBag {
     give_t: bool,
 }
@@ -377,69 +876,10 @@ impl LoveTrisNoBag {
     fn new() -> Self {
         Self { give_t: true }
     }
-
-    fn landing_y(board: &Board, x: i32, shape: &[Point; 4]) -> Option<i32> {
-        let mut last_valid: Option<i32> = None;
-        for y in 0..(TOTAL_HEIGHT as i32) {
-            let valid = shape.iter().all(|b| {
-                let px = x + b.x as i32;
-                let py = y + b.y as i32;
-                px >= 0
-                    && px < WIDTH as i32
-                    && py >= 0
-                    && py < TOTAL_HEIGHT as i32
-                    && !board.is_occupied(px, py)
-            });
-            if valid {
-                last_valid = Some(y);
-            } else {
-                break;
-            }
-        }
-        last_valid
-    }
-
-    fn best_score(board: &Board, piece: Tetromino) -> Option<(i32, usize, i32, usize, usize)> {
-        let mut best: Option<(i32, usize, i32, usize, usize)> = None;
-        for rot in [
-            Rotation::Spawn,
-            Rotation::Right,
-            Rotation::Reverse,
-            Rotation::Left,
-        ] {
-            let shape = shape_blocks(piece, rot);
-            for x in -2..WIDTH as i32 + 2 {
-                if let Some(h) = Self::landing_y(board, x, &shape) {
-                    let mut simulated = board.clone();
-                    simulated.lock_piece(x, h, &shape, piece.color_id());
-                    let lines = simulated.clear_lines() as i32;
-                    let height = simulated.max_height();
-                    let bump = simulated.bumpiness();
-                    let holes = simulated.hole_count();
-                    let score =
-                        -30 * holes as i32 - 8 * 0 - 6 * (height as i32) - 2 * bump as i32 + 10 * lines;
-                    if best
-                        .map_or(true, |(bs, bh, bl, bb, bhole)| {
-                            score > bs
-                                || (score == bs
-                                    && (height < bh
-                                        || (height == bh
-                                            && (lines > bl
-                                                || (lines == bl
-                                                    && (bump < bb || (bump == bb && holes < bhole)))))))
-                        })
-                    {
-                        best = Some((score, height, lines, bump, holes));
-                    }
-                }
-            }
-        }
-        best
-    }
 }
 
 impl Randomizer for LoveTrisNoBag {
-    fn next(&mut self, board: &Board) -> Tetromino {
+    fn next(&mut self, _board: &Board) -> Tetromino {
         // Alternate T and I pieces, ignoring the board.
         let piece = if self.give_t {
             Tetromino::T
@@ -451,6 +891,7 @@ impl Randomizer for LoveTrisNoBag {
     }
 }
 
+#[derive(Clone)]
 struct LoveTrisOriginal;
 
 impl Randomizer for LoveTrisOriginal {
@@ -475,12 +916,12 @@ impl Randomizer for LoveTrisOriginal {
             ] {
                 let shape = shape_blocks(piece, rot);
                 for x in -2..WIDTH as i32 + 2 {
-                    if let Some(h) = LoveTrisNoBag::landing_y(board, x, &shape) {
+                    if let Some(h) = board.lowest_visible_drop_height(x, &shape) {
                         let mut simulated = board.clone();
                         simulated.lock_piece(x, h, &shape, piece.color_id());
                         let _ = simulated.clear_lines();
                         let height = simulated.max_height();
-                        if best_height.map_or(true, |bh| height < bh) {
+                        if best_height.is_none_or(|bh| height < bh) {
                             best_height = Some(height);
                             best_piece = piece;
                         }
@@ -501,10 +942,14 @@ fn randomizer_from_kind(kind: RandomizerKind) -> Box<dyn Randomizer> {
         RandomizerKind::LoveTris => Box::new(LoveTris::new()),
         RandomizerKind::LoveTrisNoBag => Box::new(LoveTrisNoBag::new()),
         RandomizerKind::LoveTrisOriginal => Box::new(LoveTrisOriginal),
+        RandomizerKind::CustomPieces { shapes } => Box::new(CustomPieces {
+            slots: shapes.len().clamp(1, Tetromino::all().len()),
+            bag: Vec::new(),
+        }),
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct InputState {
     pub left: bool,
     pub right: bool,
@@ -518,23 +963,6 @@ pub struct InputState {
     pub force_i: bool,
 }
 
-impl Default for InputState {
-    fn default() -> Self {
-        Self {
-            left: false,
-            right: false,
-            soft_drop: false,
-            hard_drop: false,
-            rotate_ccw: false,
-            rotate_cw: false,
-            rotate_180: false,
-            hold: false,
-            discard: false,
-            force_i: false,
-        }
-    }
-}
-
 impl From<InputFrame> for InputState {
     fn from(value: InputFrame) -> Self {
         Self {
@@ -552,7 +980,7 @@ impl From<InputFrame> for InputState {
     }
 }
 
-fn rotate_point(p: Point, rot: Rotation) -> Point {
+const fn rotate_point(p: Point, rot: Rotation) -> Point {
     match rot {
         Rotation::Spawn => p,
         Rotation::Right => Point { x: p.y, y: -p.x },
@@ -561,7 +989,36 @@ fn rotate_point(p: Point, rot: Rotation) -> Point {
     }
 }
 
+/// Every piece's cells in every rotation, indexed by `[piece as usize][rotation as usize]`.
+const SHAPE_TABLE: [[[Point; 4]; 4]; 7] = {
+    let pieces = [
+        Tetromino::I,
+        Tetromino::J,
+        Tetromino::L,
+        Tetromino::O,
+        Tetromino::S,
+        Tetromino::Z,
+        Tetromino::T,
+    ];
+    let rotations = [Rotation::Spawn, Rotation::Right, Rotation::Reverse, Rotation::Left];
+    let mut table = [[[Point { x: 0, y: 0 }; 4]; 4]; 7];
+    let mut p = 0;
+    while p < pieces.len() {
+        let mut r = 0;
+        while r < rotations.len() {
+            table[pieces[p] as usize][rotations[r] as usize] = compute_shape_blocks(pieces[p], rotations[r]);
+            r += 1;
+        }
+        p += 1;
+    }
+    table
+};
+
 fn shape_blocks(piece: Tetromino, rotation: Rotation) -> [Point; 4] {
+    SHAPE_TABLE[piece as usize][rotation as usize]
+}
+
+const fn compute_shape_blocks(piece: Tetromino, rotation: Rotation) -> [Point; 4] {
     // Guideline SRS shapes with correct rotation centers:
     // I/O rotate about grid intersections; JLSTZ rotate about a mino center.
     match piece {
@@ -653,61 +1110,182 @@ fn shape_blocks(piece: Tetromino, rotation: Rotation) -> [Point; 4] {
                 Tetromino::I | Tetromino::O => unreachable!(),
             };
             let mut rotated = [Point { x: 0, y: 0 }; 4];
-            for (i, p) in base.iter().enumerate() {
-                rotated[i] = rotate_point(*p, rotation);
+            let mut i = 0;
+            while i < base.len() {
+                rotated[i] = rotate_point(base[i], rotation);
+                i += 1;
             }
             rotated
         }
     }
 }
 
-fn tbp_anchor_offset(piece: Tetromino, rotation: Rotation) -> Point {
-    match piece {
-        Tetromino::I => match rotation {
-            Rotation::Spawn => Point { x: 0, y: 0 },  // middle-left mino
-            Rotation::Right => Point { x: 1, y: 1 },  // middle-top mino
-            Rotation::Reverse => Point { x: 1, y: -1 }, // middle-right mino
-            Rotation::Left => Point { x: 0, y: -1 },   // middle-bottom mino
-        },
-        Tetromino::O => match rotation {
-            Rotation::Spawn => Point { x: 0, y: 0 },   // bottom-left mino
-            Rotation::Right => Point { x: 1, y: 1 },   // top-left mino
-            Rotation::Reverse => Point { x: 2, y: 0 }, // top-right mino
-            Rotation::Left => Point { x: 1, y: -1 },   // bottom-right mino
-        },
-        _ => Point { x: 0, y: 0 }, // JLTSZ centers align with the rotation origin
+fn spawn_blocks(piece: Tetromino, custom: Option<&PieceShape>) -> Blocks {
+    piece_blocks(piece, custom, Rotation::Spawn)
+}
+
+fn piece_blocks(piece: Tetromino, custom: Option<&PieceShape>, rotation: Rotation) -> Blocks {
+    match custom {
+        Some(shape) => custom_shape_blocks(shape, rotation),
+        None => shape_blocks(piece, rotation).into(),
+    }
+}
+
+fn custom_shape_blocks(shape: &PieceShape, rotation: Rotation) -> Blocks {
+    let mut cells = [Point { x: 0, y: 0 }; MAX_PIECE_BLOCKS];
+    let len = shape.blocks.len().min(MAX_PIECE_BLOCKS);
+    for (cell, &b) in cells.iter_mut().zip(&shape.blocks) {
+        *cell = rotate_point(b, rotation);
+    }
+    Blocks { cells, len }
+}
+
+/// Map a visible-field cell into the clockwise-rotated view (y up, like the field).
+fn rotate_view_point(p: Point, rotation: u16) -> Point {
+    let (w, h) = (WIDTH as i8, VISIBLE_HEIGHT as i8);
+    match rotation {
+        90 => Point { x: p.y, y: w - 1 - p.x },
+        180 => Point { x: w - 1 - p.x, y: h - 1 - p.y },
+        270 => Point { x: h - 1 - p.y, y: p.x },
+        _ => p,
+    }
+}
+
+/// Rotate a piece-relative block offset (previews) clockwise.
+fn rotate_view_offset(p: Point, rotation: u16) -> Point {
+    match rotation {
+        90 => Point { x: p.y, y: -p.x },
+        180 => Point { x: -p.x, y: -p.y },
+        270 => Point { x: -p.y, y: p.x },
+        _ => p,
+    }
+}
+
+fn rotate_view_field(field: &[u8], rotation: u16) -> Vec<u8> {
+    let view_width = if matches!(rotation, 90 | 270) { VISIBLE_HEIGHT } else { WIDTH };
+    let mut out = vec![0; field.len()];
+    for (i, &cell) in field.iter().enumerate() {
+        let p = Point {
+            x: (i % WIDTH) as i8,
+            y: (i / WIDTH) as i8,
+        };
+        let q = rotate_view_point(p, rotation);
+        out[q.y as usize * view_width + q.x as usize] = cell;
+    }
+    out
+}
+
+/// Try each SRS kick for a 90-degree rotation (only the first, `(0, 0)`, without `kicks`);
+/// returns the rotated piece and the kick used.
+fn kicked_rotation(
+    board: &Board,
+    piece: &ActivePiece,
+    cw: bool,
+    kicks_enabled: bool,
+) -> Option<(ActivePiece, (i32, i32))> {
+    let to = if cw {
+        piece.rotation.rotate_cw()
+    } else {
+        piece.rotation.rotate_ccw()
+    };
+    let kicks = match &piece.shape {
+        Some(shape) => std::iter::once((0, 0)).chain(shape.kicks.iter().copied()).collect(),
+        None => KickTable::kicks(piece.piece, piece.rotation, to),
+    };
+    let tries = if kicks_enabled { kicks.len() } else { 1 };
+    kicks
+        .into_iter()
+        .take(tries)
+        .map(|(dx, dy)| {
+            let test = ActivePiece {
+                rotation: to,
+                x: piece.x + dx,
+                y: piece.y + dy,
+                ..piece.clone()
+            };
+            (test, (dx, dy))
+        })
+        .find(|(test, _)| !board.collision(test))
+}
+
+fn ghost_cell_shown(style: GhostStyle, blocks: &[Point], cell: Point) -> bool {
+    match style {
+        GhostStyle::Full => true,
+        GhostStyle::Outline => !blocks.iter().any(|b| b.x == cell.x && b.y < cell.y),
+        GhostStyle::None => false,
     }
 }
 
-fn spawn_blocks(piece: Tetromino) -> [Point; 4] {
-    shape_blocks(piece, Rotation::Spawn)
+/// Occupied columns and shape of a piece, independent of height and rotation state, so
+/// placements that land identically (e.g. an S in either vertical state) compare equal.
+fn footprint(piece: &ActivePiece) -> Vec<(i32, i32)> {
+    let blocks = piece.blocks();
+    let min_y = blocks.iter().map(|b| b.y as i32).min().unwrap_or(0);
+    let mut cells: Vec<(i32, i32)> = blocks
+        .iter()
+        .map(|b| (piece.x + b.x as i32, b.y as i32 - min_y))
+        .collect();
+    cells.sort_unstable();
+    cells
 }
 
 #[derive(Clone)]
 struct ActivePiece {
     piece: Tetromino,
+    // Custom shape standing in for `piece` under the `CustomPieces` randomizer.
+    shape: Option<Rc<PieceShape>>,
     rotation: Rotation,
     x: i32,
     y: i32,
     lock_timer: f32,
     move_resets: u8,
+    // Set on spawn and cleared by the first advance_player tick, so a piece that
+    // appears already resting on the stack can't lock on its very first frame.
+    just_spawned: bool,
+    // Soft drop is held while grounded; tracked so the pending event fires once per piece.
+    soft_lock_pending: bool,
+    // How long soft drop has been held on the floor, for `soft_drop_lock_ms`.
+    soft_lock_held_ms: f32,
+    // Soft drop's progress beyond gravity; rows it pays out are `soft_drop_cells`.
+    soft_drop_accum: f32,
+    // Time since spawn, held against `spawn_lock_grace_ms`.
+    lock_age_ms: f32,
+    // Landed by a hard drop still inside `hard_drop_grace_ms`.
+    hard_dropped: bool,
+    // Time spent on the ground so far, held against `max_lock_time_ms`.
+    ground_ms: f32,
 }
 
 impl ActivePiece {
     fn new(piece: Tetromino) -> Self {
         Self {
             piece,
+            shape: None,
             rotation: Rotation::Spawn,
             x: 4,
             // Spawn so the lowest cells are visible; buffer row above is non-colliding.
             y: (VISIBLE_HEIGHT as i32) - 1,
             lock_timer: LOCK_DELAY_MS,
-            move_resets: 15,
+            move_resets: MOVE_RESETS,
+            just_spawned: true,
+            soft_lock_pending: false,
+            soft_lock_held_ms: 0.0,
+            soft_drop_accum: 0.0,
+            lock_age_ms: 0.0,
+            hard_dropped: false,
+            ground_ms: 0.0,
+        }
+    }
+
+    fn blocks(&self) -> Blocks {
+        match &self.shape {
+            Some(shape) => custom_shape_blocks(shape, self.rotation),
+            None => shape_blocks(self.piece, self.rotation).into(),
         }
     }
 
-    fn blocks(&self) -> [Point; 4] {
-        shape_blocks(self.piece, self.rotation)
+    fn color_id(&self) -> u8 {
+        self.shape.as_ref().map_or(self.piece.color_id(), |s| s.color)
     }
 }
 
@@ -757,7 +1335,7 @@ impl Board {
         false
     }
 
-    fn lock_piece(&mut self, x: i32, y: i32, blocks: &[Point; 4], color: u8) {
+    fn lock_piece(&mut self, x: i32, y: i32, blocks: &[Point], color: u8) {
         for b in blocks {
             let px = x + b.x as i32;
             let py = y + b.y as i32;
@@ -786,11 +1364,90 @@ impl Board {
         cleared
     }
 
+    /// Cascade gravity: settle floating groups, clear any lines they complete, and
+    /// repeat. Returns the lines cleared by each chain step after the initial clear.
+    fn cascade(&mut self) -> Vec<usize> {
+        let mut chain = Vec::new();
+        loop {
+            self.settle_groups();
+            let cleared = self.clear_lines();
+            if cleared == 0 {
+                break;
+            }
+            chain.push(cleared);
+        }
+        chain
+    }
+
+    fn settle_groups(&mut self) {
+        loop {
+            let mut moved = false;
+            for group in self.connected_groups() {
+                let cells: Vec<(usize, usize, u8)> = group
+                    .iter()
+                    .map(|&(x, y)| (x, y, self.cells[y][x]))
+                    .collect();
+                for &(x, y, _) in &cells {
+                    self.cells[y][x] = 0;
+                }
+                let mut drop = 0;
+                while cells
+                    .iter()
+                    .all(|&(x, y, _)| y > drop && self.cells[y - drop - 1][x] == 0)
+                {
+                    drop += 1;
+                }
+                for &(x, y, color) in &cells {
+                    self.cells[y - drop][x] = color;
+                }
+                moved |= drop > 0;
+            }
+            if !moved {
+                break;
+            }
+        }
+    }
+
+    // 4-connected groups of filled cells, lowest group first.
+    fn connected_groups(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut seen = [[false; WIDTH]; TOTAL_HEIGHT];
+        let mut groups = Vec::new();
+        for y in 0..TOTAL_HEIGHT {
+            for x in 0..WIDTH {
+                if seen[y][x] || self.cells[y][x] == 0 {
+                    continue;
+                }
+                let mut group = Vec::new();
+                let mut stack = vec![(x, y)];
+                seen[y][x] = true;
+                while let Some((cx, cy)) = stack.pop() {
+                    group.push((cx, cy));
+                    let neighbors = [
+                        (cx.wrapping_sub(1), cy),
+                        (cx + 1, cy),
+                        (cx, cy.wrapping_sub(1)),
+                        (cx, cy + 1),
+                    ];
+                    for (nx, ny) in neighbors {
+                        if nx < WIDTH && ny < TOTAL_HEIGHT && !seen[ny][nx] && self.cells[ny][nx] != 0 {
+                            seen[ny][nx] = true;
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+                groups.push(group);
+            }
+        }
+        groups
+    }
+
+    /// Empty visible cells covered by a filled visible cell. Buffer rows don't collide, so
+    /// cells parked there cover nothing.
     fn hole_count(&self) -> usize {
         let mut holes = 0;
         for x in 0..WIDTH {
             let mut found = false;
-            for y in (0..TOTAL_HEIGHT).rev() {
+            for y in (0..VISIBLE_HEIGHT).rev() {
                 if self.cells[y][x] != 0 {
                     found = true;
                 } else if found {
@@ -810,27 +1467,6 @@ impl Board {
         0
     }
 
-    fn column_height(&self, x: usize) -> usize {
-        for y in (0..TOTAL_HEIGHT).rev() {
-            if self.cells[y][x] != 0 {
-                return y + 1;
-            }
-        }
-        0
-    }
-
-    fn bumpiness(&self) -> usize {
-        let mut heights = [0usize; WIDTH];
-        for x in 0..WIDTH {
-            heights[x] = self.column_height(x);
-        }
-        let mut bump = 0usize;
-        for w in 0..(WIDTH - 1) {
-            bump += heights[w].max(heights[w + 1]) - heights[w].min(heights[w + 1]);
-        }
-        bump
-    }
-
     fn visible_empty(&self) -> bool {
         for y in 0..VISIBLE_HEIGHT {
             if self.cells[y].iter().any(|&c| c != 0) {
@@ -840,7 +1476,7 @@ impl Board {
         true
     }
 
-    fn lowest_drop_height(&self, x: i32, blocks: &[Point; 4]) -> Option<i32> {
+    fn lowest_drop_height(&self, x: i32, blocks: &[Point]) -> Option<i32> {
         let mut y = TOTAL_HEIGHT as i32 - 1;
         while y >= 0 {
             if blocks.iter().all(|b| {
@@ -860,20 +1496,81 @@ impl Board {
         None
     }
 
-    fn add_garbage(&mut self, lines: u32, hole: usize) -> bool {
-        if lines == 0 {
+    /// Where a piece dropped straight down in column `x` comes to rest, for AI evaluation.
+    /// Unlike `lowest_drop_height`, resting anywhere in the buffer counts as no placement.
+    fn lowest_visible_drop_height(&self, x: i32, blocks: &[Point]) -> Option<i32> {
+        let fits = |y: i32| {
+            blocks.iter().all(|b| {
+                let py = y + b.y as i32;
+                py < TOTAL_HEIGHT as i32 && !self.is_occupied(x + b.x as i32, py)
+            })
+        };
+        let mut y = (0..TOTAL_HEIGHT as i32).rev().find(|&y| fits(y))?;
+        while fits(y - 1) {
+            y -= 1;
+        }
+        blocks
+            .iter()
+            .all(|b| y + (b.y as i32) < VISIBLE_HEIGHT as i32)
+            .then_some(y)
+    }
+
+    // FNV-1a over every cell; stable across builds so hashes can be compared offline.
+    fn hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for row in self.cells.iter() {
+            for &cell in row.iter() {
+                hash ^= cell as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        hash
+    }
+
+    fn hash_hex(&self) -> String {
+        format!("{:016x}", self.hash())
+    }
+
+    /// Push garbage in from the bottom, chunk by chunk in order; every row of a chunk shares
+    /// that chunk's hole. Returns whether the stack now overflows the visible field.
+    fn add_garbage(&mut self, chunks: &[GarbageBatch]) -> bool {
+        if chunks.iter().all(|c| c.lines == 0) {
             return false;
         }
-        for _ in 0..lines {
-            for y in (1..TOTAL_HEIGHT).rev() {
-                self.cells[y] = self.cells[y - 1];
+        for chunk in chunks {
+            for _ in 0..chunk.lines {
+                for y in (1..TOTAL_HEIGHT).rev() {
+                    self.cells[y] = self.cells[y - 1];
+                }
+                let mut row = [8u8; WIDTH];
+                row[chunk.hole.min(WIDTH - 1)] = 0;
+                self.cells[0] = row;
             }
-            let mut row = [8u8; WIDTH];
-            row[hole.min(WIDTH - 1)] = 0;
-            self.cells[0] = row;
         }
         self.max_height() > VISIBLE_HEIGHT
     }
+
+    /// Rows, bottom up, holding any garbage cell. A garbage row whose hole was filled by a
+    /// piece still counts, since it stays cheese until it is cleared.
+    fn garbage_rows(&self) -> Vec<usize> {
+        (0..TOTAL_HEIGHT)
+            .filter(|&y| self.cells[y].contains(&8))
+            .collect()
+    }
+
+    /// Hole column of each garbage row from the bottom up, stopping at the first row that
+    /// isn't garbage (all 8 but for one gap).
+    #[cfg(test)]
+    fn garbage_holes(&self) -> Vec<usize> {
+        self.cells
+            .iter()
+            .map_while(|row| {
+                let hole = row.iter().position(|&c| c == 0)?;
+                let rest_is_garbage = row.iter().enumerate().all(|(x, &c)| x == hole || c == 8);
+                rest_is_garbage.then_some(hole)
+            })
+            .collect()
+    }
 }
 
 #[derive(Default)]
@@ -923,23 +1620,98 @@ impl KickTable {
 
 #[derive(Serialize)]
 pub struct PlayerStats {
+    /// Match clock: time since the game started, paused or not.
     pub time_ms: f32,
+    /// Time this player was unpaused and alive; rates (pps, apm) are measured against it.
+    pub active_play_ms: f32,
     pub pieces: u32,
     pub keys: u32,
     pub attack: u32,
     pub finesse: u32,
     pub lines_sent: u32,
+    pub holds: u32,
+    /// Attack spent cancelling incoming garbage instead of being sent.
+    pub garbage_cancelled: u32,
+    /// Attack that actually reached the opponent's garbage queue.
+    pub garbage_sent: u32,
+    pub lines_cleared_total: u32,
+    /// Consecutive pieces, up to the last one, that cleared no lines.
+    pub dry_streak: u32,
+    pub longest_dry_streak: u32,
+    pub max_combo: u32,
+    pub tetrises: u32,
+    pub t_spins: u32,
+    pub perfect_clears: u32,
+    /// Perfect clear bonus that was sent, i.e. the part of `garbage_sent` owed to perfect
+    /// clears.
+    pub pc_attack: u32,
+    /// Rows fallen because soft drop was held, beyond what gravity alone would have dropped.
+    pub soft_drop_cells: u32,
+    /// Incoming garbage rows that landed (or started rising) rather than being cancelled.
+    pub garbage_tanked: u32,
+    /// Garbage rows actually added to the field (rising rows as they rise).
+    pub garbage_received: u32,
 }
 
 impl Default for PlayerStats {
     fn default() -> Self {
         Self {
             time_ms: 0.0,
+            active_play_ms: 0.0,
             pieces: 0,
             keys: 0,
             attack: 0,
             finesse: 0,
             lines_sent: 0,
+            holds: 0,
+            garbage_cancelled: 0,
+            garbage_sent: 0,
+            lines_cleared_total: 0,
+            dry_streak: 0,
+            longest_dry_streak: 0,
+            max_combo: 0,
+            tetrises: 0,
+            t_spins: 0,
+            perfect_clears: 0,
+            pc_attack: 0,
+            soft_drop_cells: 0,
+            garbage_tanked: 0,
+            garbage_received: 0,
+        }
+    }
+}
+
+impl PlayerStats {
+    fn pps(&self) -> f32 {
+        let time_s = if self.active_play_ms > 0.0 { self.active_play_ms / 1000.0 } else { 0.0 };
+        if time_s > 0.0 {
+            self.pieces as f32 / time_s
+        } else {
+            0.0
+        }
+    }
+
+    fn kpp(&self) -> f32 {
+        if self.pieces > 0 {
+            self.keys as f32 / self.pieces as f32
+        } else {
+            0.0
+        }
+    }
+
+    fn apm(&self) -> f32 {
+        if self.active_play_ms > 0.0 {
+            self.attack as f32 / (self.active_play_ms / 60_000.0)
+        } else {
+            0.0
+        }
+    }
+
+    fn lines_per_piece(&self) -> f32 {
+        if self.pieces > 0 {
+            self.lines_cleared_total as f32 / self.pieces as f32
+        } else {
+            0.0
         }
     }
 }
@@ -947,6 +1719,7 @@ impl Default for PlayerStats {
 #[derive(Serialize)]
 pub struct PlayerStatsView {
     pub time_ms: f32,
+    pub active_play_ms: f32,
     pub pieces: u32,
     pub keys: u32,
     pub attack: u32,
@@ -954,7 +1727,137 @@ pub struct PlayerStatsView {
     pub pps: f32,
     pub kpp: f32,
     pub lines_sent: u32,
+    pub garbage_cancelled: u32,
+    pub garbage_sent: u32,
     pub pending_garbage: u32,
+    pub garbage_received: u32,
+    /// `garbage_sent` minus `garbage_received`.
+    pub net_garbage: i64,
+}
+
+#[derive(Clone, Copy, Serialize, Debug, PartialEq, Eq)]
+pub enum TopOutReason {
+    /// The next piece spawned overlapping the stack.
+    BlockOut,
+    /// A piece locked entirely or partly above the visible field.
+    LockOut,
+    /// Incoming garbage pushed the stack out of the field.
+    GarbageOut,
+}
+
+/// Post-game breakdown for one player, as returned by `finalResults`.
+#[derive(Serialize)]
+pub struct FinalResults {
+    pub time_ms: f32,
+    pub active_play_ms: f32,
+    pub pieces: u32,
+    pub pps: f32,
+    pub kpp: f32,
+    pub apm: f32,
+    pub attack: u32,
+    pub lines_sent: u32,
+    pub max_combo: u32,
+    pub tetrises: u32,
+    pub t_spins: u32,
+    pub perfect_clears: u32,
+    pub finesse_faults: u32,
+    /// `None` while the player is still alive (or won).
+    pub top_out: Option<TopOutReason>,
+}
+
+#[derive(Serialize)]
+pub struct PieceDrought {
+    pub piece: Tetromino,
+    pub pieces_since: u32,
+}
+
+#[derive(Serialize)]
+pub struct DetailedStats {
+    pub holds: u32,
+    /// Pieces dealt since each piece type was last dealt, one entry per type.
+    pub droughts: Vec<PieceDrought>,
+    pub i_drought: u32,
+    pub longest_drought: u32,
+    pub lines_per_piece: f32,
+    /// Most pieces placed in a row without clearing a line.
+    pub longest_dry_streak: u32,
+}
+
+/// Why a direct state setter (`setField`, `setQueue`) rejected its input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    InvalidPlayer,
+    InvalidDimensions { width: usize, height: usize },
+    WrongLength { expected: usize, got: usize },
+    InvalidColor(u8),
+    InvalidPiece(u8),
+    /// The new field overlaps the active piece, which was not reset.
+    OverlapsActive,
+    /// The (re)spawned active piece would collide immediately.
+    WouldTopOut,
+}
+
+impl StateError {
+    fn code(&self) -> &'static str {
+        match self {
+            StateError::InvalidPlayer => "invalid_player",
+            StateError::InvalidDimensions { .. } => "invalid_dimensions",
+            StateError::WrongLength { .. } => "wrong_length",
+            StateError::InvalidColor(_) => "invalid_color",
+            StateError::InvalidPiece(_) => "invalid_piece",
+            StateError::OverlapsActive => "overlaps_active",
+            StateError::WouldTopOut => "would_top_out",
+        }
+    }
+}
+
+impl std::fmt::Display for StateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateError::InvalidPlayer => write!(f, "invalid player index"),
+            StateError::InvalidDimensions { width, height } => write!(
+                f,
+                "field must be {} wide and at most {} tall, got {}x{}",
+                WIDTH, VISIBLE_HEIGHT, width, height
+            ),
+            StateError::WrongLength { expected, got } => {
+                write!(f, "expected {} cells, got {}", expected, got)
+            }
+            StateError::InvalidColor(id) => write!(f, "invalid cell color id {}", id),
+            StateError::InvalidPiece(id) => write!(f, "invalid piece id {}", id),
+            StateError::OverlapsActive => write!(f, "field overlaps the active piece"),
+            StateError::WouldTopOut => write!(f, "active piece would spawn inside the stack"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorView {
+    code: &'static str,
+    message: String,
+}
+
+impl From<StateError> for JsValue {
+    fn from(err: StateError) -> JsValue {
+        let view = ErrorView {
+            code: err.code(),
+            message: err.to_string(),
+        };
+        to_value(&view).unwrap_or_else(|_| JsValue::from_str(&view.message))
+    }
+}
+
+#[derive(Serialize)]
+pub struct Dimensions {
+    pub width: usize,
+    pub visible_height: usize,
+    pub buffer_height: usize,
+}
+
+#[derive(Serialize)]
+pub struct FinesseHint {
+    pub inputs: Vec<String>,
+    pub keys: u32,
 }
 
 #[derive(Serialize, Clone)]
@@ -965,26 +1868,137 @@ pub struct LineClearSummary {
 
 #[derive(Serialize)]
 pub struct PlayerView {
+    /// Left empty (and omitted) by `tickDelta` once the frontend has a full field cached.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub field: Vec<u8>,
+    /// `[cell index, color id]` for cells that changed since the previous `tickDelta`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed: Option<Vec<[u16; 2]>>,
     pub active: Vec<Point>,
     pub active_color: u8,
     pub active_piece: u8,
     pub active_rotation: String,
+    /// How far the lock delay has run for a grounded piece: 0 on landing (or after a reset),
+    /// 1 at lock. Always 0 while airborne.
+    pub lock_progress: f32,
     pub ghost: Vec<Point>,
     pub hold: Option<u8>,
     pub hold_blocks: Option<Vec<Point>>,
     pub hold_color_id: Option<u8>,
+    /// False once hold has been used for the current piece.
+    pub hold_available: bool,
     pub next: Vec<u8>,
     pub next_blocks: Vec<Vec<Point>>,
     pub topped_out: bool,
+    /// Consecutive clearing pieces, 1 on the first clear of a chain.
+    pub combo: u32,
+    pub combo_display: u32,
     pub stats: PlayerStatsView,
     pub summary: Vec<LineClearSummary>,
 }
 
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type")]
+pub enum GameEvent {
+    /// Cascade gravity chained `chain` extra clears worth `lines` lines after a lock.
+    CascadeClear { player: usize, chain: u32, lines: u32 },
+    /// Soft drop is held on the floor; the piece locks in `remaining_ms` (or at once with
+    /// `soft_drop_lock`).
+    SoftLockPending { player: usize, remaining_ms: f32 },
+    /// One garbage row with its gap at `hole` rose into the field; `remaining` are still queued.
+    GarbageRising { player: usize, hole: usize, remaining: u32 },
+    /// The stack reached `height` rows, at or past `danger_threshold`. Fires again only after
+    /// the stack has dropped `DANGER_HYSTERESIS_ROWS` below the threshold.
+    DangerZone { player: usize, height: usize },
+    /// What the T-spin classifier saw for a lock (every lock, with `spin_debug`).
+    SpinDebug {
+        player: usize,
+        #[serde(flatten)]
+        debug: SpinDebug,
+    },
+    /// `piece` is neither queued nor left in the current bag, so at least `pieces_until`
+    /// other pieces spawn first. Raised once per drought.
+    DroughtWarning { player: usize, piece: Tetromino, pieces_until: u32 },
+    /// A piece locked; `inputs` are the presses since the previous lock, in order (at most
+    /// `PIECE_INPUTS_LEN`). Only with `record_inputs`.
+    PieceLocked { player: usize, inputs: Vec<InputButton> },
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SpinDebug {
+    pub piece: Tetromino,
+    /// Top-left, top-right, bottom-left, bottom-right of the piece center, after locking.
+    pub corners: [bool; 4],
+    pub last_rotation: bool,
+    /// Offset of the kick that placed the piece on its last rotation.
+    pub last_kick: (i32, i32),
+    /// The classifier's verdict, before the needs-a-line-clear rule.
+    pub spin: bool,
+}
+
+const DANGER_HYSTERESIS_ROWS: usize = 2;
+
+#[derive(Clone, Copy, Deserialize, Debug)]
+pub struct ScriptedAttack {
+    pub time_ms: f32,
+    pub lines: u32,
+}
+
+/// How player 0 dealt with a loaded garbage script so far.
+#[derive(Serialize)]
+pub struct GarbageScriptReport {
+    pub attacks_delivered: u32,
+    pub attacks_remaining: u32,
+    pub lines_scripted: u32,
+    pub cancelled: u32,
+    pub tanked: u32,
+    /// Delivered but neither cancelled nor landed yet.
+    pub pending: u32,
+}
+
+#[derive(Clone, Copy, Serialize, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum GameResult {
+    Win { player: usize },
+    /// Both players topped out in the same tick.
+    Draw,
+}
+
 #[derive(Serialize)]
 pub struct FrameView {
+    pub result: Option<GameResult>,
     pub players: Vec<PlayerView>,
     pub settings: GameSettings,
+    pub events: Vec<GameEvent>,
+    pub controller_kind: Vec<ControllerKind>,
+    /// Pre-game countdown left; play starts at 0.
+    pub countdown_ms: f32,
+}
+
+#[derive(Serialize)]
+pub struct BroadcastPlayer {
+    /// Visible field, bottom row first, always upright and unmirrored.
+    pub field: Vec<u8>,
+    pub topped_out: bool,
+    pub controller_kind: ControllerKind,
+    /// The opponent whose garbage is queued against this player, if any.
+    pub attacked_by: Option<usize>,
+    /// Landed garbage rows still waiting to rise into the field.
+    pub rising_garbage: u32,
+    pub stats: PlayerStatsView,
+}
+
+#[derive(Serialize)]
+pub struct BroadcastView {
+    pub result: Option<GameResult>,
+    pub players: Vec<BroadcastPlayer>,
+}
+
+#[derive(Clone, Copy, Serialize, Debug, PartialEq, Eq)]
+pub enum ControllerKind {
+    Human,
+    InternalBot,
+    ExternalBot,
 }
 
 #[derive(Serialize)]
@@ -996,6 +2010,44 @@ pub struct AppliedMoveResult {
     pub new_queue_piece: Option<tbp_data::Piece>,
     pub combo: u32,
     pub back_to_back: bool,
+    /// The move's piece was not the active one, so the engine assumed the bot held to get it.
+    pub inferred_hold: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MovePreview {
+    /// `false` when the move can't be placed or would top the player out.
+    pub legal: bool,
+    pub lines: usize,
+    pub spin: bool,
+    pub resulting_hash: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayReport {
+    /// One result per move applied, up to (not including) the first failure.
+    pub results: Vec<AppliedMoveResult>,
+    pub failed_at: Option<usize>,
+    pub reason: Option<String>,
+    pub board_hash: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TbpHold {
+    None,
+    // Swap the active piece with the held one.
+    Swap,
+    // Hold the active piece and take the next piece from the queue.
+    TakeNext,
+}
+
+struct TbpPlacement {
+    active: ActivePiece,
+    hold: TbpHold,
+    spin: bool,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -1025,34 +2077,101 @@ impl Default for ControlBindings {
     }
 }
 
+#[derive(Clone)]
 struct Player {
     board: Board,
     active: ActivePiece,
     queue: Vec<Tetromino>,
     hold: Option<Tetromino>,
     held_on_turn: bool,
+    // T-spin eligibility: set by a successful rotation, cleared by any successful shift
+    // (including DAS) or gravity/soft-drop step. Hard drop keeps it, so only a rotation
+    // immediately before the lock counts.
     last_action_was_rotation: bool,
     last_kick: (i32, i32),
     randomizer: Box<dyn Randomizer>,
     randomizer_kind: RandomizerKind,
     topped_out: bool,
+    top_out_reason: Option<TopOutReason>,
     top_out_on_spawn: bool,
     pending_garbage: Vec<GarbageBatch>,
+    // Landed garbage rows (by hole column) waiting to rise when rising is enabled.
+    rising_garbage: VecDeque<usize>,
+    rise_timer: f32,
     combo: u32,
     back_to_back: bool,
     last_refill_added: Option<Tetromino>,
     recent_events: Vec<LineClearSummary>,
+    last_cascade: Vec<usize>,
+    // Spin classifier inputs of the last lock, when `spin_debug` is on.
+    last_spin_debug: Option<SpinDebug>,
+    // Whether the last lock was a T-spin mini (scored from the mini row of the attack table).
+    last_lock_mini: bool,
+    // The piece as it locked, for scoring its finesse.
+    last_locked: Option<ActivePiece>,
+    dealt_history: Vec<Tetromino>,
+    queue_len: usize,
+    // Pieces dealt ahead of the randomizer (puzzle setups) until exhausted.
+    forced_queue: VecDeque<Tetromino>,
+    // Shapes standing in for each tetromino slot under `CustomPieces`; empty otherwise.
+    custom_shapes: Vec<Rc<PieceShape>>,
+    // Frames left before garbage held back by `spawn_safe_frame` lands; 0 when none is.
+    garbage_delay_frames: u32,
+    // State just after the most recent line clear, for `undo_to_last_clear`. Only kept for
+    // human players.
+    last_clear: Option<ClearCheckpoint>,
+}
+
+#[derive(Clone)]
+struct ClearCheckpoint {
+    board: Board,
+    active: ActivePiece,
+    queue: Vec<Tetromino>,
+    forced_queue: VecDeque<Tetromino>,
+    randomizer: Box<dyn Randomizer>,
+    hold: Option<Tetromino>,
+    combo: u32,
+    back_to_back: bool,
+    pending_garbage: Vec<GarbageBatch>,
+    rising_garbage: VecDeque<usize>,
+    dealt_history: Vec<Tetromino>,
+}
+
+const DEALT_HISTORY_LEN: usize = 64;
+
+const DEFAULT_LOOKAHEAD_DEPTH: usize = 6;
+
+fn custom_shapes(kind: &RandomizerKind) -> Vec<Rc<PieceShape>> {
+    match kind {
+        RandomizerKind::CustomPieces { shapes } => shapes.iter().cloned().map(Rc::new).collect(),
+        _ => Vec::new(),
+    }
 }
+const PACKED_VERSION: u8 = 1;
 
 impl Player {
-    fn new(randomizer_kind: RandomizerKind, top_out_on_spawn: bool) -> Self {
+    /// `initial` pieces are dealt first (the first one spawns), then the randomizer takes over.
+    fn new(
+        randomizer_kind: RandomizerKind,
+        top_out_on_spawn: bool,
+        queue_len: usize,
+        initial: &[Tetromino],
+    ) -> Self {
+        let queue_len = queue_len.max(1);
         let mut randomizer = randomizer_from_kind(randomizer_kind.clone());
+        let mut forced_queue: VecDeque<Tetromino> = initial.iter().copied().collect();
         let mut queue = Vec::new();
-        for _ in 0..6 {
-            queue.push(randomizer.next(&Board::new()));
+        for _ in 0..queue_len {
+            let piece = match forced_queue.pop_front() {
+                Some(piece) => piece,
+                None => randomizer.next(&Board::new()),
+            };
+            queue.push(piece);
         }
+        let dealt_history = queue.clone();
         let first = queue.remove(0);
-        Self {
+        let custom_shapes = custom_shapes(&randomizer_kind);
+        let mut player = Self {
             board: Board::new(),
             active: ActivePiece::new(first),
             queue,
@@ -1063,16 +2182,72 @@ impl Player {
             randomizer,
             randomizer_kind,
             topped_out: false,
+            top_out_reason: None,
             top_out_on_spawn,
             pending_garbage: Vec::new(),
+            rising_garbage: VecDeque::new(),
+            rise_timer: 0.0,
             combo: 0,
             back_to_back: false,
             last_refill_added: None,
             recent_events: Vec::new(),
+            last_cascade: Vec::new(),
+            last_spin_debug: None,
+            last_lock_mini: false,
+            last_locked: None,
+            dealt_history,
+            queue_len,
+            forced_queue,
+            custom_shapes,
+            garbage_delay_frames: 0,
+            last_clear: None,
+        };
+        player.active = player.new_piece(first);
+        player
+    }
+
+    fn checkpoint(&self) -> ClearCheckpoint {
+        ClearCheckpoint {
+            board: self.board.clone(),
+            active: self.active.clone(),
+            queue: self.queue.clone(),
+            forced_queue: self.forced_queue.clone(),
+            randomizer: self.randomizer.clone_box(),
+            hold: self.hold,
+            combo: self.combo,
+            back_to_back: self.back_to_back,
+            pending_garbage: self.pending_garbage.clone(),
+            rising_garbage: self.rising_garbage.clone(),
+            dealt_history: self.dealt_history.clone(),
         }
     }
 
+    fn custom_shape(&self, piece: Tetromino) -> Option<&Rc<PieceShape>> {
+        self.custom_shapes.get(piece.color_id() as usize - 1)
+    }
+
+    /// A fresh spawn of `piece`, or of the custom shape in its slot.
+    fn new_piece(&self, piece: Tetromino) -> ActivePiece {
+        let mut active = ActivePiece::new(piece);
+        active.shape = self.custom_shape(piece).cloned();
+        active
+    }
+
+    fn piece_color(&self, piece: Tetromino) -> u8 {
+        self.custom_shape(piece).map_or(piece.color_id(), |s| s.color)
+    }
+
+    fn spawn_blocks(&self, piece: Tetromino) -> Blocks {
+        spawn_blocks(piece, self.custom_shape(piece).map(|s| &**s))
+    }
+
+    /// Switch randomizers, restarting the queue. Re-selecting the current kind keeps the
+    /// bag and queue as they are.
     fn set_randomizer(&mut self, kind: RandomizerKind) {
+        if kind == self.randomizer_kind {
+            return;
+        }
+        self.custom_shapes = custom_shapes(&kind);
         self.randomizer_kind = kind.clone();
         self.randomizer = randomizer_from_kind(kind);
         self.queue.clear();
@@ -1083,10 +2258,91 @@ impl Player {
 
     fn refill_queue(&mut self) {
         self.last_refill_added = None;
-        while self.queue.len() < 6 {
-            let piece = self.randomizer.next(&self.board);
+        while self.queue.len() < self.queue_len {
+            let piece = match self.forced_queue.pop_front() {
+                Some(piece) => piece,
+                None => self.randomizer.next(&self.board),
+            };
             self.queue.push(piece);
             self.last_refill_added = Some(piece);
+            self.dealt_history.push(piece);
+        }
+        if self.dealt_history.len() > DEALT_HISTORY_LEN {
+            let drop = self.dealt_history.len() - DEALT_HISTORY_LEN;
+            self.dealt_history.drain(0..drop);
+        }
+    }
+
+    /// Under `SevenBag`, pieces absent from both the queue and what's left of the bag, with
+    /// the fewest pieces that must spawn before each can: the queue plus the rest of the bag.
+    fn guaranteed_droughts(&self) -> Option<Vec<(Tetromino, u32)>> {
+        if !matches!(self.randomizer_kind, RandomizerKind::SevenBag) {
+            return None;
+        }
+        let bag = self.randomizer.bag_state()?;
+        let ahead = self.queue.len() + self.forced_queue.len() + bag.len();
+        Some(
+            Tetromino::all()
+                .into_iter()
+                .filter(|p| !self.queue.contains(p) && !self.forced_queue.contains(p) && !bag.contains(p))
+                .map(|p| (p, ahead as u32))
+                .collect(),
+        )
+    }
+
+    // Pieces dealt since `piece` was last dealt; capped at the history length when unseen.
+    fn drought(&self, piece: Tetromino) -> u32 {
+        self.dealt_history
+            .iter()
+            .rev()
+            .position(|p| *p == piece)
+            .unwrap_or(self.dealt_history.len()) as u32
+    }
+
+    /// Cancel up to `lines` of pending garbage, oldest first; returns what was left over.
+    fn cancel_pending_garbage(&mut self, mut lines: u32) -> u32 {
+        let pending = &mut self.pending_garbage;
+        while lines > 0 && !pending.is_empty() {
+            if lines >= pending[0].lines {
+                lines -= pending[0].lines;
+                pending.remove(0);
+            } else {
+                pending[0].lines -= lines;
+                lines = 0;
+            }
+        }
+        lines
+    }
+
+    fn lock_progress(&self) -> f32 {
+        if self.topped_out || !self.on_ground() {
+            return 0.0;
+        }
+        (1.0 - self.active.lock_timer / LOCK_DELAY_MS).clamp(0.0, 1.0)
+    }
+
+    fn on_ground(&self) -> bool {
+        let test = ActivePiece {
+            y: self.active.y - 1,
+            ..self.active.clone()
+        };
+        self.board.collision(&test)
+    }
+
+    fn top_out(&mut self, reason: TopOutReason) {
+        if !self.topped_out {
+            self.top_out_reason = Some(reason);
+        }
+        self.topped_out = true;
+    }
+
+    /// After garbage lands: top out if it overflowed the field or, for players who top out
+    /// on spawn, buried the spawn position so the next piece couldn't appear.
+    fn check_garbage_top_out(&mut self, overflow: bool) {
+        let spawn_blocked =
+            self.top_out_on_spawn && self.board.collision(&self.new_piece(self.active.piece));
+        if overflow || spawn_blocked {
+            self.top_out(TopOutReason::GarbageOut);
         }
     }
 
@@ -1095,14 +2351,19 @@ impl Player {
         self.last_action_was_rotation = false;
         let next_piece = self.queue.remove(0);
         self.refill_queue();
-        self.active = ActivePiece::new(next_piece);
+        self.active = self.new_piece(next_piece);
         if self.top_out_on_spawn && self.board.collision(&self.active) {
-            self.topped_out = true;
+            self.top_out(TopOutReason::BlockOut);
             log("Top out on spawn");
         }
     }
 
-    fn hard_drop(&mut self) -> (usize, bool, bool) {
+    fn hard_drop(&mut self, settings: &GameSettings) -> (usize, bool, bool) {
+        self.drop_to_floor();
+        self.lock_piece(settings)
+    }
+
+    fn drop_to_floor(&mut self) {
         let mut landing_y = self.active.y;
         loop {
             let test = ActivePiece {
@@ -1119,26 +2380,75 @@ impl Player {
             }
         }
         self.active.y = landing_y;
-        self.lock_piece()
     }
 
-    fn lock_piece(&mut self) -> (usize, bool, bool) {
-        let color = self.active.piece.color_id();
+    /// Hold as the TBP move asks and put its piece in place, ready to lock.
+    fn take_tbp_placement(&mut self, placement: TbpPlacement) {
+        match placement.hold {
+            TbpHold::None => {}
+            TbpHold::Swap => {
+                self.hold = Some(self.active.piece);
+                self.held_on_turn = true;
+            }
+            TbpHold::TakeNext => {
+                self.hold = Some(self.active.piece);
+                self.queue.remove(0);
+                self.refill_queue();
+                self.held_on_turn = true;
+            }
+        }
+        self.active = placement.active;
+        if placement.spin {
+            self.last_action_was_rotation = true;
+            self.last_kick = (2, 1); // treat as a kicked rotation to satisfy mini rule if needed
+        }
+    }
+
+    fn lock_piece(&mut self, settings: &GameSettings) -> (usize, bool, bool) {
+        let color = self.active.color_id();
         let blocks = self.active.blocks();
         let mut overflow = false;
         self.board
             .lock_piece(self.active.x, self.active.y, &blocks, color);
         let potential_t_spin =
             detect_t_spin(&self.board, &self.active, self.last_action_was_rotation, self.last_kick);
-        let cleared = self.board.clear_lines();
-        for b in blocks {
-            let py = self.active.y + b.y as i32;
-            if py >= VISIBLE_HEIGHT as i32 || py < 0 {
-                overflow = true;
-                break;
+        let potential_mini =
+            detect_t_spin_mini(&self.board, &self.active, self.last_action_was_rotation, self.last_kick);
+        self.last_spin_debug = settings.spin_debug.then(|| SpinDebug {
+            piece: self.active.piece,
+            corners: t_corners(&self.board, &self.active),
+            last_rotation: self.last_action_was_rotation,
+            last_kick: self.last_kick,
+            spin: potential_t_spin,
+        });
+        let mut cleared = self.board.clear_lines();
+        let was_t_spin = potential_t_spin && cleared > 0;
+        self.last_lock_mini = potential_mini && cleared > 0;
+        self.last_locked = Some(self.active.clone());
+        self.last_cascade.clear();
+        if cleared > 0 && settings.clear_gravity == ClearGravity::Cascade {
+            self.last_cascade = self.board.cascade();
+            cleared += self.last_cascade.iter().sum::<usize>();
+        }
+        match settings.lock_out_rule {
+            LockOutRule::Lenient => {
+                for b in blocks {
+                    let py = self.active.y + b.y as i32;
+                    if py >= VISIBLE_HEIGHT as i32 || py < 0 {
+                        overflow = true;
+                        break;
+                    }
+                }
+            }
+            LockOutRule::Strict => {
+                if blocks
+                    .iter()
+                    .all(|b| self.active.y + b.y as i32 >= VISIBLE_HEIGHT as i32)
+                {
+                    self.top_out(TopOutReason::LockOut);
+                }
             }
         }
-        let was_t_spin = potential_t_spin && cleared > 0;
         self.spawn_next();
         (cleared, was_t_spin, overflow)
     }
@@ -1146,6 +2456,26 @@ impl Player {
 
 impl Versus {
     fn on_piece_locked(&mut self, idx: usize, cleared: usize, is_t_spin: bool, overflow: bool) {
+        let inputs = std::mem::take(&mut self.controllers[idx].piece_inputs);
+        if let Some(locked) = self.players[idx].last_locked.take()
+            && self.is_finesse_fault(idx, &locked, &inputs)
+        {
+            self.stats[idx].finesse = self.stats[idx].finesse.saturating_add(1);
+        }
+        if self.settings.record_inputs {
+            self.events.push(GameEvent::PieceLocked { player: idx, inputs });
+        }
+        if let Some(debug) = self.players[idx].last_spin_debug.take() {
+            self.events.push(GameEvent::SpinDebug { player: idx, debug });
+        }
+        if !self.players[idx].last_cascade.is_empty() {
+            let chain = &self.players[idx].last_cascade;
+            self.events.push(GameEvent::CascadeClear {
+                player: idx,
+                chain: chain.len() as u32,
+                lines: chain.iter().sum::<usize>() as u32,
+            });
+        }
         // Work with locals to avoid aliasing self borrows.
         let attack_out: u32;
         let mut apply_garbage = false;
@@ -1156,18 +2486,42 @@ impl Versus {
 
             if cleared > 0 {
                 player.combo = player.combo.saturating_add(1);
+                stats.lines_cleared_total = stats.lines_cleared_total.saturating_add(cleared as u32);
+                stats.dry_streak = 0;
             } else {
                 player.combo = 0;
                 apply_garbage = true;
+                stats.dry_streak = stats.dry_streak.saturating_add(1);
+                stats.longest_dry_streak = stats.longest_dry_streak.max(stats.dry_streak);
             }
 
-            let perfect_clear = player.board.visible_empty();
-            let mut attack = if is_t_spin && cleared > 0 {
+            // Any clear that leaves the field empty is a perfect clear, a single included, and
+            // earns the flat `perfect_clear` bonus on top of the clear's own attack.
+            let perfect_clear = cleared > 0 && player.board.visible_empty();
+            let mut attack = if let AttackMode::PerLine {
+                per_line,
+                offset,
+                t_spin_multiplier,
+            } = self.settings.attack_mode
+            {
+                let base = (cleared as i64 * per_line as i64 + offset as i64).max(0) as u32;
+                if is_t_spin && cleared > 0 {
+                    base.saturating_mul(t_spin_multiplier)
+                } else {
+                    base
+                }
+            } else if is_t_spin && cleared > 0 {
                 match cleared {
                     1 => self.attack_table.t_spin_single as u32,
                     2 => self.attack_table.t_spin_double as u32,
                     _ => self.attack_table.t_spin_triple as u32,
                 }
+            } else if player.last_lock_mini && cleared > 0 {
+                match cleared {
+                    1 => self.attack_table.t_spin_mini_single as u32,
+                    2 => self.attack_table.t_spin_mini_double as u32,
+                    _ => self.attack_table.t_spin_mini_triple as u32,
+                }
             } else {
                 match cleared {
                     0 => self.attack_table._0_lines as u32,
@@ -1178,7 +2532,12 @@ impl Versus {
                 }
             };
             let base_attack = attack;
-            let combo_idx = player.combo.saturating_sub(1);
+            // Index by the internal counter so display settings never shift attack.
+            let combo_for_attack = match self.settings.combo.max_combo_for_attack {
+                Some(max) => player.combo.min(max),
+                None => player.combo,
+            };
+            let combo_idx = combo_for_attack.saturating_sub(1);
             let combo_bonus = match combo_idx {
                 0 => self.combo_table.c0,
                 1 => self.combo_table.c1,
@@ -1210,24 +2569,31 @@ impl Versus {
             }
             let attack_before_cancel = attack;
             player.back_to_back = difficult;
-
-            if attack > 0 {
-                let pending = &mut player.pending_garbage;
-                while attack > 0 && !pending.is_empty() {
-                    if let Some(front) = pending.first_mut() {
-                        if attack >= front.lines {
-                        attack -= front.lines;
-                        pending.remove(0);
-                    } else {
-                        front.lines -= attack;
-                        attack = 0;
-                    }
-                }
+            stats.max_combo = stats.max_combo.max(player.combo);
+            if cleared >= 4 {
+                stats.tetrises = stats.tetrises.saturating_add(1);
+            }
+            if is_t_spin && cleared > 0 {
+                stats.t_spins = stats.t_spins.saturating_add(1);
+            }
+            if perfect_clear {
+                stats.perfect_clears = stats.perfect_clears.saturating_add(1);
             }
-        }
 
-            attack_out = attack;
             stats.attack = stats.attack.saturating_add(attack_before_cancel);
+            if self.settings.versus_enabled {
+                if attack > 0 && !self.settings.garbage.send_then_cancel {
+                    attack = player.cancel_pending_garbage(attack);
+                }
+                attack_out = attack;
+                // Cancelling eats the rest of the attack before the perfect clear bonus.
+                stats.pc_attack = stats.pc_attack.saturating_add(pc_bonus.min(attack_out));
+                stats.garbage_cancelled = stats
+                    .garbage_cancelled
+                    .saturating_add(attack_before_cancel - attack_out);
+            } else {
+                attack_out = 0;
+            }
 
             // Summaries: record any line clear (attack or not).
             if cleared > 0 {
@@ -1269,66 +2635,145 @@ impl Versus {
             }
         }
 
-        // Apply any blocked garbage now that combo is broken.
-        if apply_garbage {
-            let pending_batches = std::mem::take(&mut self.players[idx].pending_garbage);
-            let mut overflow = false;
-            for batch in pending_batches {
-                if self.players[idx].board.add_garbage(batch.lines, batch.hole) {
-                    overflow = true;
-                }
-            }
-            if overflow {
-                self.players[idx].topped_out = true;
-            }
+        if cleared > 0 && self.human[idx] {
+            let checkpoint = self.players[idx].checkpoint();
+            self.players[idx].last_clear = Some(checkpoint);
+        }
+
+        // Apply any blocked garbage now that combo is broken. TBP-driven players have no
+        // frames to wait out, so theirs always lands at once.
+        let ticked = self.human[idx] || self.drives_internal_bot(idx);
+        let apply_garbage = apply_garbage && self.settings.versus_enabled;
+        if apply_garbage && self.settings.garbage.spawn_safe_frame && ticked {
+            // This frame, then one safe frame for the new piece.
+            self.players[idx].garbage_delay_frames = 2;
+        } else if apply_garbage {
+            self.land_pending_garbage(idx);
         }
 
         // Deliver outgoing attack after previous borrows are released.
         if attack_out > 0 {
             let opp = if idx == 0 { 1 } else { 0 };
-            let mut rng = thread_rng();
-            let hole = rng.gen_range(0..WIDTH);
-            self.players[opp].pending_garbage.push(GarbageBatch {
-                lines: attack_out,
-                hole,
-            });
+            self.queue_garbage(opp, attack_out);
             self.stats[idx].lines_sent = self.stats[idx].lines_sent.saturating_add(attack_out);
+            self.stats[idx].garbage_sent = self.stats[idx].garbage_sent.saturating_add(attack_out);
         }
 
         if overflow && idx == 0 {
-            self.players[idx].topped_out = true;
+            self.players[idx].top_out(TopOutReason::LockOut);
         }
     }
 
-}
+    /// Queue incoming garbage for a player; it is applied when their combo breaks.
+    fn queue_garbage(&mut self, idx: usize, lines: u32) {
+        let hole = match self.settings.garbage.fixed_hole {
+            Some(col) => col,
+            None => with_rng(|rng| rng.gen_range(0..WIDTH)),
+        };
+        self.players[idx]
+            .pending_garbage
+            .push(GarbageBatch { lines, hole });
+    }
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
-pub struct InputFrame {
-    pub left: bool,
-    pub right: bool,
-    pub soft_drop: bool,
-    pub hard_drop: bool,
-    pub rotate_ccw: bool,
-    pub rotate_cw: bool,
-    pub rotate_180: bool,
-    pub hold: bool,
-    pub discard: bool,
-    pub force_i: bool,
+    /// Move pending garbage onto the board, or into the rising queue when rising is enabled.
+    /// Tops the player out on overflow.
+    fn land_pending_garbage(&mut self, idx: usize) {
+        let rising = self.settings.garbage.rise_interval_ms > 0.0;
+        let player = &mut self.players[idx];
+        let batches = std::mem::take(&mut player.pending_garbage);
+        let lines: u32 = batches.iter().map(|b| b.lines).sum();
+        let stats = &mut self.stats[idx];
+        stats.garbage_tanked = stats.garbage_tanked.saturating_add(lines);
+        if rising {
+            for batch in batches {
+                player
+                    .rising_garbage
+                    .extend(std::iter::repeat_n(batch.hole, batch.lines as usize));
+            }
+        } else {
+            let overflow = player.board.add_garbage(&batches);
+            stats.garbage_received = stats.garbage_received.saturating_add(lines);
+            player.check_garbage_top_out(overflow);
+        }
+    }
+
+    /// Land garbage held back by `spawn_safe_frame`, lifting the piece clear of it.
+    fn land_deferred_garbage(&mut self, idx: usize) {
+        self.land_pending_garbage(idx);
+        let player = &mut self.players[idx];
+        while !player.topped_out && player.board.collision(&player.active) {
+            if player.active.y >= TOTAL_HEIGHT as i32 {
+                player.top_out(TopOutReason::GarbageOut);
+                break;
+            }
+            player.active.y += 1;
+        }
+    }
+
+    /// Raise queued garbage rows one at a time, nudging the active piece up if a row lands
+    /// under it.
+    fn advance_garbage_rise(&mut self, idx: usize, dt_ms: f32) {
+        let interval = self.settings.garbage.rise_interval_ms;
+        let player = &mut self.players[idx];
+        if player.rising_garbage.is_empty() {
+            player.rise_timer = 0.0;
+            return;
+        }
+        player.rise_timer += dt_ms;
+        while player.rise_timer >= interval && !player.topped_out {
+            let Some(hole) = player.rising_garbage.pop_front() else {
+                break;
+            };
+            player.rise_timer -= interval;
+            let overflow = player.board.add_garbage(&[GarbageBatch { lines: 1, hole }]);
+            let stats = &mut self.stats[idx];
+            stats.garbage_received = stats.garbage_received.saturating_add(1);
+            if player.board.collision(&player.active) {
+                player.active.y += 1;
+            }
+            player.check_garbage_top_out(overflow);
+            self.events.push(GameEvent::GarbageRising {
+                player: idx,
+                hole,
+                remaining: player.rising_garbage.len() as u32,
+            });
+        }
+    }
+
+    fn inject_garbage(&mut self, idx: usize, lines: u32) -> Result<(), String> {
+        let player = self.players.get(idx).ok_or("invalid player index")?;
+        if player.topped_out {
+            return Err("player topped out".into());
+        }
+        if lines > 0 {
+            self.queue_garbage(idx, lines);
+        }
+        Ok(())
+    }
 }
 
-impl Default for InputFrame {
-    fn default() -> Self {
-        InputFrame {
-            left: false,
-            right: false,
-            soft_drop: false,
-            hard_drop: false,
-            rotate_ccw: false,
-            rotate_cw: false,
-            rotate_180: false,
-            hold: false,
-            discard: false,
-            force_i: false,
+#[derive(Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+pub struct InputFrame {
+    pub left: bool,
+    pub right: bool,
+    pub soft_drop: bool,
+    pub hard_drop: bool,
+    pub rotate_ccw: bool,
+    pub rotate_cw: bool,
+    pub rotate_180: bool,
+    pub hold: bool,
+    pub discard: bool,
+    pub force_i: bool,
+}
+
+impl InputFrame {
+    fn mirrored(self) -> Self {
+        Self {
+            left: self.right,
+            right: self.left,
+            rotate_ccw: self.rotate_cw,
+            rotate_cw: self.rotate_ccw,
+            ..self
         }
     }
 }
@@ -1350,28 +2795,44 @@ impl From<InputState> for InputFrame {
     }
 }
 
-fn count_input_edges(prev: &InputState, curr: &InputState) -> u32 {
-    let mut edges = 0;
+#[derive(Clone, Copy, Serialize, Debug, PartialEq, Eq)]
+pub enum InputButton {
+    Left,
+    Right,
+    SoftDrop,
+    HardDrop,
+    RotateCcw,
+    RotateCw,
+    Rotate180,
+    Hold,
+    Discard,
+    ForceI,
+}
+
+/// Buttons pressed between `prev` and `curr`, in a fixed order.
+fn input_edges(prev: &InputState, curr: &InputState) -> Vec<InputButton> {
     let fields = [
-        (prev.left, curr.left),
-        (prev.right, curr.right),
-        (prev.soft_drop, curr.soft_drop),
-        (prev.hard_drop, curr.hard_drop),
-        (prev.rotate_ccw, curr.rotate_ccw),
-        (prev.rotate_cw, curr.rotate_cw),
-        (prev.rotate_180, curr.rotate_180),
-        (prev.hold, curr.hold),
-        (prev.discard, curr.discard),
-        (prev.force_i, curr.force_i),
+        (InputButton::Left, prev.left, curr.left),
+        (InputButton::Right, prev.right, curr.right),
+        (InputButton::SoftDrop, prev.soft_drop, curr.soft_drop),
+        (InputButton::HardDrop, prev.hard_drop, curr.hard_drop),
+        (InputButton::RotateCcw, prev.rotate_ccw, curr.rotate_ccw),
+        (InputButton::RotateCw, prev.rotate_cw, curr.rotate_cw),
+        (InputButton::Rotate180, prev.rotate_180, curr.rotate_180),
+        (InputButton::Hold, prev.hold, curr.hold),
+        (InputButton::Discard, prev.discard, curr.discard),
+        (InputButton::ForceI, prev.force_i, curr.force_i),
     ];
-    for (p, c) in fields {
-        if !p && c {
-            edges += 1;
-        }
-    }
-    edges
+    fields
+        .into_iter()
+        .filter(|(_, p, c)| !p && *c)
+        .map(|(button, _, _)| button)
+        .collect()
 }
 
+// Cap on the presses kept for one piece; a piece stalled with inputs stops recording.
+const PIECE_INPUTS_LEN: usize = 64;
+
 struct Controller {
     inputs: InputState,
     last_hard_drop: bool,
@@ -1382,8 +2843,14 @@ struct Controller {
     last_rotate_cw: bool,
     last_rotate_ccw: bool,
     last_rotate_180: bool,
+    last_hold: bool,
     last_discard: bool,
     last_force_i: bool,
+    // Simulated input latency: frames wait here for `input_delay` ticks before applying.
+    input_delay: usize,
+    delayed: VecDeque<InputFrame>,
+    // Presses since the last lock, reported with `PieceLocked`.
+    piece_inputs: Vec<InputButton>,
 }
 
 impl Controller {
@@ -1398,12 +2865,34 @@ impl Controller {
             last_rotate_cw: false,
             last_rotate_ccw: false,
             last_rotate_180: false,
+            last_hold: false,
             last_discard: false,
             last_force_i: false,
+            input_delay: 0,
+            delayed: VecDeque::new(),
+            piece_inputs: Vec::new(),
         }
     }
 
     fn update_inputs(&mut self, incoming: InputFrame) {
+        if self.input_delay == 0 && self.delayed.is_empty() {
+            self.apply_inputs(incoming);
+            return;
+        }
+        self.delayed.push_back(incoming);
+        // After the delay is lowered, catch up by skipping frames identical to the one behind
+        // them; every other frame still gets its own tick so no press or release is lost.
+        while self.delayed.len() > self.input_delay + 1 && self.delayed[0] == self.delayed[1] {
+            self.delayed.pop_front();
+        }
+        if self.delayed.len() > self.input_delay
+            && let Some(frame) = self.delayed.pop_front()
+        {
+            self.apply_inputs(frame);
+        }
+    }
+
+    fn apply_inputs(&mut self, incoming: InputFrame) {
         self.inputs.left = incoming.left;
         self.inputs.right = incoming.right;
         self.inputs.soft_drop = incoming.soft_drop;
@@ -1440,6 +2929,12 @@ impl Controller {
         fire
     }
 
+    fn take_hold(&mut self) -> bool {
+        let fire = self.inputs.hold && !self.last_hold;
+        self.last_hold = self.inputs.hold;
+        fire
+    }
+
     fn take_discard(&mut self) -> bool {
         let fire = self.inputs.discard && !self.last_discard;
         self.last_discard = self.inputs.discard;
@@ -1453,19 +2948,70 @@ impl Controller {
     }
 }
 
-struct BotConfig {
-    pps: f32,
+/// The bot's speed moves linearly from `pps_start` to `pps_end` over `ramp_seconds` of its
+/// game time, then holds at `pps_end`. `smooth` bots tap one action at a time, spread over
+/// the piece's time, instead of thinking for a piece's time and then sliding over with DAS.
+#[derive(Clone, Copy, Debug)]
+pub struct BotConfig {
+    pub pps_start: f32,
+    pub pps_end: f32,
+    pub ramp_seconds: f32,
+    pub smooth: bool,
+    pub tie_break: BotTieBreak,
+}
+
+/// Which of several equally low columns the internal bot picks.
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq, Eq)]
+pub enum BotTieBreak {
+    #[default]
+    LeftToRight,
+    /// Nearest the middle of the field, left of center first.
+    CenterOut,
 }
 
 impl Default for BotConfig {
     fn default() -> Self {
-        Self { pps: 1.8 }
+        Self::constant(1.8)
+    }
+}
+
+impl BotConfig {
+    pub fn constant(pps: f32) -> Self {
+        Self {
+            pps_start: pps,
+            pps_end: pps,
+            ramp_seconds: 0.0,
+            smooth: false,
+            tie_break: BotTieBreak::LeftToRight,
+        }
+    }
+
+    fn pps_at(&self, elapsed_ms: f32) -> f32 {
+        if self.pps_start == self.pps_end || self.ramp_seconds <= 0.0 {
+            return self.pps_end;
+        }
+        let t = (elapsed_ms / 1000.0 / self.ramp_seconds).clamp(0.0, 1.0);
+        self.pps_start + (self.pps_end - self.pps_start) * t
     }
 }
 
+// Where the bot has decided to put its current piece.
+#[derive(Clone, Copy)]
+struct BotTarget {
+    piece: Tetromino,
+    x: i32,
+    rotate_cw: bool,
+}
+
 struct BotDriver {
     config: BotConfig,
     think_timer: f32,
+    // Placement being worked toward; the bot walks there with held inputs over several ticks.
+    target: Option<BotTarget>,
+    rotated: bool,
+    released_drop: bool,
+    // Smooth mode: time between taps for the current piece.
+    step_ms: f32,
 }
 
 impl BotDriver {
@@ -1473,45 +3019,143 @@ impl BotDriver {
         Self {
             config,
             think_timer: 0.0,
+            target: None,
+            rotated: false,
+            released_drop: true,
+            step_ms: 0.0,
         }
     }
 
-    fn update(&mut self, player: &mut Player, dt_ms: f32) -> InputFrame {
-        let mut frame = InputFrame {
-            left: false,
-            right: false,
-            soft_drop: false,
-            hard_drop: false,
-            rotate_ccw: false,
-            rotate_cw: false,
-            rotate_180: false,
-            hold: false,
-            discard: false,
-            force_i: false,
-        };
+    fn update(&mut self, player: &mut Player, dt_ms: f32, elapsed_ms: f32) -> InputFrame {
+        if self.config.smooth {
+            return self.update_smooth(player, dt_ms, elapsed_ms);
+        }
+        let mut frame = InputFrame::default();
         self.think_timer += dt_ms;
-        let piece_time = 1000.0 / self.config.pps.max(0.1);
-        if self.think_timer >= piece_time {
+        // Hard drop is edge-triggered, so let go of it for a frame after every drop.
+        if !self.released_drop {
+            self.released_drop = true;
+            return frame;
+        }
+        if self
+            .target
+            .is_some_and(|t| t.piece != player.active.piece)
+        {
+            self.target = None;
+        }
+        if self.target.is_none() {
+            let piece_time = 1000.0 / self.config.pps_at(elapsed_ms).max(0.1);
+            if self.think_timer < piece_time {
+                return frame;
+            }
             self.think_timer = 0.0;
-            let best = find_safe_column(&player.board, player.active.piece);
-            if let Some(plan) = best {
-                frame = plan;
-            } else {
-                frame.hard_drop = true;
+            self.rotated = false;
+            self.target = Some(
+                find_safe_column(&player.board, player.active.piece, self.config.tie_break).unwrap_or(BotTarget {
+                    piece: player.active.piece,
+                    x: player.active.x,
+                    rotate_cw: false,
+                }),
+            );
+        }
+        let Some(target) = self.target else {
+            return frame;
+        };
+        if target.rotate_cw && !self.rotated {
+            frame.rotate_cw = true;
+            self.rotated = true;
+            return frame;
+        }
+        // Hold the direction and let the controller's DAS/ARR carry the piece over.
+        let dir = (target.x - player.active.x).signum();
+        let blocked = dir != 0
+            && player.board.collision(&ActivePiece {
+                x: player.active.x + dir,
+                ..player.active.clone()
+            });
+        if dir == 0 || blocked {
+            frame.hard_drop = true;
+            self.target = None;
+            self.released_drop = false;
+        } else if dir < 0 {
+            frame.left = true;
+        } else {
+            frame.right = true;
+        }
+        frame
+    }
+
+    // One tap per step, each followed by a released frame so the next registers as a fresh
+    // press and DAS never kicks in. The taps are spaced so the drop lands on the PPS pace.
+    fn update_smooth(&mut self, player: &Player, dt_ms: f32, elapsed_ms: f32) -> InputFrame {
+        let mut frame = InputFrame::default();
+        self.think_timer += dt_ms;
+        if !self.released_drop {
+            self.released_drop = true;
+            return frame;
+        }
+        if self
+            .target
+            .is_some_and(|t| t.piece != player.active.piece)
+        {
+            self.target = None;
+        }
+        let target = match self.target {
+            Some(target) => target,
+            None => {
+                let target = find_safe_column(&player.board, player.active.piece, self.config.tie_break).unwrap_or(BotTarget {
+                    piece: player.active.piece,
+                    x: player.active.x,
+                    rotate_cw: false,
+                });
+                let taps = target.rotate_cw as i32 + (target.x - player.active.x).abs() + 1;
+                let piece_time = 1000.0 / self.config.pps_at(elapsed_ms).max(0.1);
+                self.step_ms = piece_time / taps as f32;
+                self.think_timer = 0.0;
+                self.rotated = false;
+                self.target = Some(target);
+                target
             }
+        };
+        if self.think_timer < self.step_ms {
+            return frame;
+        }
+        self.think_timer -= self.step_ms;
+        self.released_drop = false;
+        if target.rotate_cw && !self.rotated {
+            frame.rotate_cw = true;
+            self.rotated = true;
+            return frame;
+        }
+        let dir = (target.x - player.active.x).signum();
+        let blocked = dir != 0
+            && player.board.collision(&ActivePiece {
+                x: player.active.x + dir,
+                ..player.active.clone()
+            });
+        if dir == 0 || blocked {
+            frame.hard_drop = true;
+            self.target = None;
+        } else if dir < 0 {
+            frame.left = true;
+        } else {
+            frame.right = true;
         }
         frame
     }
 }
 
-fn find_safe_column(board: &Board, piece: Tetromino) -> Option<InputFrame> {
-    let mut rng = thread_rng();
-    let mut columns: Vec<i32> = (0..WIDTH as i32).collect();
-    columns.shuffle(&mut rng);
-
+fn find_safe_column(board: &Board, piece: Tetromino, tie_break: BotTieBreak) -> Option<BotTarget> {
+    // Lowest column wins; ties go to the first in `tie_break` order so the choice is
+    // reproducible.
+    let mut cols: Vec<i32> = (0..WIDTH as i32).collect();
+    if tie_break == BotTieBreak::CenterOut {
+        let center = (WIDTH as i32 - 1) as f32 / 2.0;
+        cols.sort_by(|a, b| (*a as f32 - center).abs().total_cmp(&(*b as f32 - center).abs()));
+    }
     let mut best_col: Option<i32> = None;
     let mut best_height = usize::MAX;
-    for col in columns {
+    for col in cols {
         let height = (0..TOTAL_HEIGHT)
             .rev()
             .find(|&y| board.cells[y][col as usize] != 0)
@@ -1523,30 +3167,72 @@ fn find_safe_column(board: &Board, piece: Tetromino) -> Option<InputFrame> {
         }
     }
 
-    if let Some(col) = best_col {
-        let mut frame = InputFrame {
-            left: false,
-            right: false,
-            soft_drop: false,
-            hard_drop: true,
-            rotate_ccw: false,
-            rotate_cw: false,
-            rotate_180: false,
-            hold: false,
-            discard: false,
-            force_i: false,
-        };
-        if col < 4 {
-            frame.left = true;
-        } else if col > 4 {
-            frame.right = true;
-        }
-        if piece == Tetromino::I && best_height + 4 > VISIBLE_HEIGHT + BUFFER_HEIGHT - 2 {
-            frame.rotate_cw = true;
+    best_col.map(|col| BotTarget {
+        piece,
+        x: col,
+        rotate_cw: piece == Tetromino::I && best_height + 4 > VISIBLE_HEIGHT + BUFFER_HEIGHT - 2,
+    })
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Placement {
+    pub x: i32,
+    pub y: i32,
+    pub rotation: Rotation,
+    pub lines: usize,
+}
+
+/// Outcome of a headless `simulate_bot` run.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct BotBenchmark {
+    pub pieces: u32,
+    pub lines: u32,
+    pub survival_ms: f32,
+    pub max_height: usize,
+    /// `false` when the run hit `max_pieces` (or stalled) first.
+    pub topped_out: bool,
+}
+
+/// Let the internal bot play player 1 alone with `bot`, without rendering, until it tops out
+/// or has placed `max_pieces`. For benchmarking bot heuristics: pieces and garbage holes
+/// are drawn from `seed`, so the same arguments always give the same result.
+pub fn simulate_bot(
+    settings: GameSettings,
+    randomizer: RandomizerKind,
+    bot: BotConfig,
+    seed: u64,
+    max_pieces: u32,
+) -> BotBenchmark {
+    let previous = GAME_RNG.replace(StdRng::seed_from_u64(seed));
+    let result = run_bot_benchmark(settings, randomizer, bot, max_pieces);
+    GAME_RNG.set(previous);
+    result
+}
+
+fn run_bot_benchmark(settings: GameSettings, randomizer: RandomizerKind, bot: BotConfig, max_pieces: u32) -> BotBenchmark {
+    const FRAME_MS: f32 = 1000.0 / 60.0;
+    let mut vs = Versus::new(settings, bot, [randomizer.clone(), randomizer]);
+    vs.use_internal_bot = true;
+    vs.human = [false, false];
+    // A bot that stops placing pieces must not spin forever: allow a minute per piece.
+    let max_ticks = max_pieces as u64 * 3600;
+    let mut max_height = 0;
+    for _ in 0..max_ticks {
+        if vs.players[1].topped_out || vs.stats[1].pieces >= max_pieces {
+            break;
         }
-        return Some(frame);
+        vs.tick2(FRAME_MS, InputFrame::default(), InputFrame::default());
+        vs.events.clear();
+        max_height = max_height.max(vs.players[1].board.max_height());
+    }
+    let stats = &vs.stats[1];
+    BotBenchmark {
+        pieces: stats.pieces,
+        lines: stats.lines_cleared_total,
+        survival_ms: stats.time_ms,
+        max_height,
+        topped_out: vs.players[1].topped_out,
     }
-    None
 }
 
 struct Versus {
@@ -1555,72 +3241,310 @@ struct Versus {
     settings: GameSettings,
     bot_driver: BotDriver,
     use_internal_bot: bool,
+    // Players that have had a TBP move applied, i.e. are driven by an external bot.
+    tbp_driven: [bool; 2],
+    // Players fed keyboard input by `tick2`; the rest are the internal bot or TBP-driven.
+    human: [bool; 2],
+    // A TBP move whose exact y collides is an error rather than dropped to the lowest fit.
+    strict_tbp: bool,
     fall_accum: [f32; 2],
     gravity_ms: f32,
     stats: [PlayerStats; 2],
     last_inputs: [InputState; 2],
     attack_table: AttackTable,
     combo_table: ComboTable,
+    // Events raised since the last frame was handed to the frontend.
+    events: Vec<GameEvent>,
+    // Frozen players skip input, gravity, lock delay and garbage rise; the other plays on.
+    paused: [bool; 2],
+    tick_count: u64,
+    // Tick on which each player topped out, to tell a mutual KO from a win.
+    top_out_ticks: [Option<u64>; 2],
+    // Scripted attacks against player 0 still to come, earliest first.
+    garbage_script: VecDeque<ScriptedAttack>,
+    garbage_script_len: usize,
+    garbage_script_lines: u32,
+    // Whether each player's last DangerZone is still standing.
+    in_danger: [bool; 2],
+    // Last tick's falling, per player, while `fall_debug` is on.
+    fall_debug: [FallDebug; 2],
+    // Pieces each player has had a DroughtWarning for that haven't been queued since.
+    drought_warned: [Vec<Tetromino>; 2],
+    // Pre-game countdown still to run.
+    countdown_ms: f32,
+}
+
+/// How the active piece fell on a player's last tick.
+#[derive(Clone, Copy, Serialize, Debug, Default)]
+pub struct FallDebug {
+    pub gravity_rows: u32,
+    /// Rows beyond gravity owed to soft drop.
+    pub soft_drop_rows: u32,
+    pub fall_accum: f32,
+    pub soft_drop_accum: f32,
 }
 
 impl Versus {
     fn new(settings: GameSettings, bot_config: BotConfig, randomizers: [RandomizerKind; 2]) -> Self {
+        let initial = settings.initial_queue.as_deref().unwrap_or_default();
+        let countdown_ms = settings.countdown_ms;
         Self {
             players: [
-                Player::new(randomizers[0].clone(), false),
-                Player::new(randomizers[1].clone(), true),
+                Player::new(randomizers[0].clone(), false, settings.lookahead_depth, initial),
+                Player::new(randomizers[1].clone(), true, settings.lookahead_depth, initial),
             ],
             controllers: [Controller::new(), Controller::new()],
             settings,
             bot_driver: BotDriver::new(bot_config),
             use_internal_bot: false, // external bot is expected by default; can be toggled on if desired
+            tbp_driven: [false, false],
+            human: [true, false],
+            strict_tbp: false,
             fall_accum: [0.0, 0.0],
             gravity_ms: 1000.0,
             stats: [PlayerStats::default(), PlayerStats::default()],
             last_inputs: [InputState::default(), InputState::default()],
             attack_table: default_attack_table(),
             combo_table: default_combo_table(),
+            events: Vec::new(),
+            paused: [false, false],
+            tick_count: 0,
+            top_out_ticks: [None, None],
+            garbage_script: VecDeque::new(),
+            garbage_script_len: 0,
+            garbage_script_lines: 0,
+            in_danger: [false, false],
+            fall_debug: [FallDebug::default(); 2],
+            drought_warned: [Vec::new(), Vec::new()],
+            countdown_ms,
+        }
+    }
+
+    /// Countdown DAS: track the held direction without shifting, so the first frame of play
+    /// makes the initial shift and auto-repeats at once if DAS has charged.
+    fn charge_das(&mut self, idx: usize, dt_ms: f32) {
+        let ctrl = &mut self.controllers[idx];
+        let dir = match (ctrl.inputs.left, ctrl.inputs.right) {
+            (true, false) => -1,
+            (false, true) => 1,
+            _ => 0,
+        };
+        if dir != ctrl.last_dir {
+            ctrl.das_timer = 0.0;
+            ctrl.arr_timer = 0.0;
+            ctrl.shifted_initial = false;
+            ctrl.last_dir = dir;
+        }
+        if dir != 0 {
+            ctrl.das_timer = (ctrl.das_timer + dt_ms).min(self.settings.das as f32);
         }
     }
 
+    /// `tick2` with keys for player 0 only.
+    #[cfg(test)]
     fn tick(&mut self, dt_ms: f32, input0: InputFrame) {
+        self.tick2(dt_ms, input0, InputFrame::default());
+    }
+
+    /// `tick` with keyboard input for both players; each `input` is ignored unless that
+    /// player is marked human.
+    fn tick2(&mut self, dt_ms: f32, input0: InputFrame, input1: InputFrame) {
+        // Top-outs between ticks (TBP moves, injected garbage) count as the next tick's.
+        self.record_top_outs();
         if self.players[0].topped_out || self.players[1].topped_out {
             return;
         }
-        for s in self.stats.iter_mut() {
+        if self.countdown_ms > 0.0 {
+            self.countdown_ms = (self.countdown_ms - dt_ms).max(0.0);
+            for (idx, input) in [input0, input1].into_iter().enumerate() {
+                if !self.human[idx] {
+                    continue;
+                }
+                let input = if self.settings.mirror { input.mirrored() } else { input };
+                self.controllers[idx].update_inputs(input);
+                self.charge_das(idx, dt_ms);
+                // A hard drop held through "go" must be pressed again.
+                self.controllers[idx].take_hard_drop();
+                self.last_inputs[idx] = input.into();
+            }
+            return;
+        }
+        for (idx, s) in self.stats.iter_mut().enumerate() {
             s.time_ms += dt_ms;
+            if !self.paused[idx] && !self.players[idx].topped_out {
+                s.active_play_ms += dt_ms;
+            }
         }
-        self.controllers[0].update_inputs(input0);
-        self.stats[0].keys += count_input_edges(&self.last_inputs[0], &input0.clone().into());
-        self.last_inputs[0] = input0.into();
-        if self.use_internal_bot {
-            let bot_input = self.bot_driver.update(&mut self.players[1], dt_ms);
-            self.controllers[1].update_inputs(bot_input);
-            self.stats[1].keys +=
-                count_input_edges(&self.last_inputs[1], &bot_input.clone().into());
-            self.last_inputs[1] = bot_input.into();
-        } else {
-            let idle = InputFrame::default();
-            self.controllers[1].update_inputs(idle);
+        while let Some(attack) = self.garbage_script.front()
+            && attack.time_ms <= self.stats[0].active_play_ms
+        {
+            let lines = attack.lines;
+            self.garbage_script.pop_front();
+            if lines > 0 {
+                self.queue_garbage(0, lines);
+            }
+        }
+        for (idx, input) in [input0, input1].into_iter().enumerate() {
+            // A paused bot keeps its controller and think timer as they were.
+            if self.paused[idx] {
+                continue;
+            }
+            let input = if self.human[idx] {
+                if self.settings.mirror {
+                    input.mirrored()
+                } else {
+                    input
+                }
+            } else if self.drives_internal_bot(idx) {
+                let elapsed = self.stats[1].active_play_ms;
+                self.bot_driver.update(&mut self.players[1], dt_ms, elapsed)
+            } else {
+                self.controllers[idx].update_inputs(InputFrame::default());
+                continue;
+            };
+            self.controllers[idx].update_inputs(input);
+            let edges = input_edges(&self.last_inputs[idx], &input.into());
+            self.stats[idx].keys += edges.len() as u32;
+            let recorded = &mut self.controllers[idx].piece_inputs;
+            let room = PIECE_INPUTS_LEN.saturating_sub(recorded.len());
+            recorded.extend(edges.into_iter().take(room));
+            self.last_inputs[idx] = input.into();
         }
 
         for idx in 0..2 {
-            if idx == 1 && !self.use_internal_bot {
+            if self.paused[idx] {
                 continue;
             }
-            let is_bot = idx == 1;
+            self.advance_garbage_rise(idx, dt_ms);
+            // Players with neither keys nor the internal bot only move via TBP.
+            if !self.human[idx] && !self.drives_internal_bot(idx) {
+                continue;
+            }
+            let is_bot = !self.human[idx];
             let inputs = self.controllers[idx].inputs.clone();
             self.advance_player(idx, dt_ms, inputs, is_bot);
         }
+        self.check_danger();
+        self.check_droughts();
+        self.record_top_outs();
+        self.tick_count += 1;
+    }
+
+    fn check_danger(&mut self) {
+        let threshold = (self.settings.danger_threshold * VISIBLE_HEIGHT as f32).ceil() as usize;
+        let rearm = threshold.saturating_sub(DANGER_HYSTERESIS_ROWS);
+        for idx in 0..2 {
+            let height = self.players[idx].board.max_height();
+            if !self.in_danger[idx] && height >= threshold {
+                self.in_danger[idx] = true;
+                self.events.push(GameEvent::DangerZone { player: idx, height });
+            } else if self.in_danger[idx] && height < rearm {
+                self.in_danger[idx] = false;
+            }
+        }
+    }
+
+    /// Replace the garbage script; attacks are queued against player 0 once its active play
+    /// time reaches each `time_ms`, whatever order they are given in.
+    fn load_garbage_script(&mut self, mut attacks: Vec<ScriptedAttack>) {
+        attacks.sort_by(|a, b| a.time_ms.total_cmp(&b.time_ms));
+        self.garbage_script_len = attacks.len();
+        self.garbage_script_lines = attacks.iter().map(|a| a.lines).sum();
+        self.garbage_script = attacks.into();
+    }
+
+    fn garbage_script_report(&self) -> GarbageScriptReport {
+        let stats = &self.stats[0];
+        GarbageScriptReport {
+            attacks_delivered: (self.garbage_script_len - self.garbage_script.len()) as u32,
+            attacks_remaining: self.garbage_script.len() as u32,
+            lines_scripted: self.garbage_script_lines,
+            cancelled: stats.garbage_cancelled,
+            tanked: stats.garbage_tanked,
+            pending: self.players[0].pending_garbage.iter().map(|b| b.lines).sum(),
+        }
+    }
+
+    fn check_droughts(&mut self) {
+        let Some(threshold) = self.settings.drought_warning_pieces else {
+            return;
+        };
+        for idx in 0..2 {
+            let player = &self.players[idx];
+            let Some(gaps) = player.guaranteed_droughts() else {
+                continue;
+            };
+            let warned = &mut self.drought_warned[idx];
+            warned.retain(|p| gaps.iter().any(|(q, _)| q == p));
+            for (piece, pieces_until) in gaps {
+                if pieces_until >= threshold && !warned.contains(&piece) {
+                    warned.push(piece);
+                    self.events.push(GameEvent::DroughtWarning { player: idx, piece, pieces_until });
+                }
+            }
+        }
+    }
+
+    fn drives_internal_bot(&self, idx: usize) -> bool {
+        idx == 1 && self.use_internal_bot && !self.human[idx]
+    }
+
+    fn set_human(&mut self, idx: usize, human: bool) -> Result<(), String> {
+        *self.human.get_mut(idx).ok_or("invalid player index")? = human;
+        Ok(())
+    }
+
+    fn record_top_outs(&mut self) {
+        for (player, tick) in self.players.iter().zip(self.top_out_ticks.iter_mut()) {
+            if player.topped_out && tick.is_none() {
+                *tick = Some(self.tick_count);
+            }
+        }
+    }
+
+    /// `None` while both players are alive; a draw when both topped out on the same tick.
+    fn result(&self) -> Option<GameResult> {
+        match self.top_out_ticks {
+            [None, None] => None,
+            [Some(a), Some(b)] if a == b => Some(GameResult::Draw),
+            [Some(a), Some(b)] => Some(GameResult::Win {
+                player: if a < b { 1 } else { 0 },
+            }),
+            [Some(_), None] => Some(GameResult::Win { player: 1 }),
+            [None, Some(_)] => Some(GameResult::Win { player: 0 }),
+        }
     }
 
     fn advance_player(&mut self, idx: usize, dt_ms: f32, inputs: InputState, _is_bot: bool) {
         if self.players[idx].topped_out {
             return;
         }
+        if self.settings.fall_debug {
+            // Ticks that lock or discard before gravity report no rows.
+            self.fall_debug[idx] = FallDebug::default();
+        }
+        if self.players[idx].garbage_delay_frames > 0 {
+            self.players[idx].garbage_delay_frames -= 1;
+            if self.players[idx].garbage_delay_frames == 0 {
+                self.land_deferred_garbage(idx);
+                if self.players[idx].topped_out {
+                    return;
+                }
+            }
+        }
         let (mut moved, mut rotated) = (false, false);
         if self.controllers[idx].take_hard_drop() {
-            let (cleared, t_spin, overflow) = self.players[idx].hard_drop();
+            let grace = self.settings.hard_drop_grace_ms;
+            if grace > 0.0 && self.human[idx] && !self.players[idx].active.hard_dropped {
+                let player = &mut self.players[idx];
+                player.drop_to_floor();
+                player.active.hard_dropped = true;
+                player.active.lock_timer = grace;
+                player.active.move_resets = 0;
+                self.fall_accum[idx] = 0.0;
+                return;
+            }
+            let (cleared, t_spin, overflow) = self.players[idx].hard_drop(&self.settings);
             self.on_piece_locked(idx, cleared, t_spin, overflow);
             self.fall_accum[idx] = 0.0;
             return;
@@ -1665,7 +3589,16 @@ impl Versus {
                 shifted_initial = true;
             }
             das_timer += dt_ms;
-            if das_timer >= self.settings.das as f32 {
+            let instant = match self.settings.arr_mode {
+                ArrMode::Auto => self.settings.arr == 0,
+                ArrMode::Stepped => false,
+                ArrMode::Instant => true,
+            };
+            if das_timer >= self.settings.das as f32 && instant {
+                while self.try_shift(idx, dir) {
+                    moved = true;
+                }
+            } else if das_timer >= self.settings.das as f32 {
                 arr_timer += dt_ms;
                 let step = self.settings.arr.max(1) as f32;
                 while arr_timer >= step {
@@ -1685,50 +3618,104 @@ impl Versus {
         self.controllers[idx].arr_timer = arr_timer;
         self.controllers[idx].shifted_initial = shifted_initial;
 
-        if inputs.hold {
+        if self.controllers[idx].take_hold() {
             self.try_hold(idx);
         }
 
-        // Gravity / soft drop
-        let drop_speed = if inputs.soft_drop {
-            self.settings.soft_drop.factor()
-        } else {
-            1.0
-        };
-        self.fall_accum[idx] += dt_ms * drop_speed;
+        // Gravity, then the extra rows owed only to soft drop, so those can be counted apart.
+        self.fall_accum[idx] += dt_ms;
+        let mut gravity_rows = 0;
         while self.fall_accum[idx] >= self.gravity_ms {
             if !self.try_fall(idx) {
                 break;
             }
             self.fall_accum[idx] -= self.gravity_ms;
+            gravity_rows += 1;
         }
-
-        let on_ground = {
-            let test = ActivePiece {
-                y: self.players[idx].active.y - 1,
-                ..self.players[idx].active.clone()
+        let mut soft_drop_rows = 0;
+        let mut soft_dropped = false;
+        if inputs.soft_drop {
+            let factor = match self.settings.soft_drop_mode {
+                SoftDropMode::Multiplier => self.settings.soft_drop.factor(),
+                SoftDropMode::Absolute { ms_per_row } => (self.gravity_ms / ms_per_row).max(1.0),
             };
-            self.players[idx].board.collision(&test)
-        };
+            let extra = dt_ms * (factor - 1.0);
+            self.players[idx].active.soft_drop_accum += extra;
+            while self.players[idx].active.soft_drop_accum >= self.gravity_ms {
+                if !self.try_fall(idx) {
+                    self.players[idx].active.soft_drop_accum = 0.0;
+                    break;
+                }
+                self.players[idx].active.soft_drop_accum -= self.gravity_ms;
+                soft_dropped = true;
+                soft_drop_rows += 1;
+                let stats = &mut self.stats[idx];
+                stats.soft_drop_cells = stats.soft_drop_cells.saturating_add(1);
+            }
+        } else {
+            self.players[idx].active.soft_drop_accum = 0.0;
+        }
+        if self.settings.fall_debug {
+            self.fall_debug[idx] = FallDebug {
+                gravity_rows,
+                soft_drop_rows,
+                fall_accum: self.fall_accum[idx],
+                soft_drop_accum: self.players[idx].active.soft_drop_accum,
+            };
+        }
+
+        let on_ground = self.players[idx].on_ground();
 
         let piece = &mut self.players[idx].active;
-        if rotated || moved {
-            if on_ground && piece.move_resets > 0 {
-                piece.lock_timer = LOCK_DELAY_MS;
-                piece.move_resets -= 1;
-            }
+        let soft_drop_reset = soft_dropped && self.settings.soft_drop_resets_lock;
+        if (rotated || moved || soft_drop_reset) && on_ground && piece.move_resets > 0 {
+            piece.lock_timer = LOCK_DELAY_MS;
+            piece.move_resets -= 1;
         }
 
+        let just_spawned = std::mem::replace(&mut piece.just_spawned, false);
+        piece.lock_age_ms += dt_ms;
+        let soft_locking = on_ground && inputs.soft_drop;
+        if soft_locking && !piece.soft_lock_pending {
+            let held_lock = self.settings.soft_drop_lock_ms.unwrap_or(f32::INFINITY);
+            self.events.push(GameEvent::SoftLockPending {
+                player: idx,
+                remaining_ms: piece.lock_timer.min(held_lock),
+            });
+        }
+        piece.soft_lock_pending = soft_locking;
+        if !soft_locking {
+            piece.soft_lock_held_ms = 0.0;
+        }
         if on_ground {
-            piece.lock_timer -= dt_ms;
-            if piece.lock_timer <= 0.0 {
-                let (cleared, t_spin, overflow) = self.players[idx].lock_piece();
+            if !just_spawned {
+                piece.lock_timer -= dt_ms;
+                // Soft drop on the floor locks at once instead of waiting out the delay.
+                if soft_locking && self.settings.soft_drop_lock {
+                    piece.lock_timer = 0.0;
+                }
+                // Or, with `soft_drop_lock_ms`, once it has been held there that long.
+                if soft_locking && let Some(ms) = self.settings.soft_drop_lock_ms {
+                    piece.soft_lock_held_ms += dt_ms;
+                    if piece.soft_lock_held_ms >= ms {
+                        piece.lock_timer = 0.0;
+                    }
+                }
+                // Resets can't stall past the total cap.
+                piece.ground_ms += dt_ms;
+                if piece.ground_ms >= self.settings.max_lock_time_ms {
+                    piece.lock_timer = 0.0;
+                }
+            }
+            if piece.lock_timer <= 0.0 && piece.lock_age_ms >= self.settings.spawn_lock_grace_ms {
+                let (cleared, t_spin, overflow) = self.players[idx].lock_piece(&self.settings);
                 self.on_piece_locked(idx, cleared, t_spin, overflow);
                 self.fall_accum[idx] = 0.0;
             }
         } else {
             piece.lock_timer = LOCK_DELAY_MS;
-            piece.move_resets = 15;
+            piece.move_resets = MOVE_RESETS;
+            piece.hard_dropped = false;
         }
     }
 
@@ -1765,25 +3752,107 @@ impl Versus {
             let second = self.try_rotate(idx, cw, false);
             return first || second;
         }
-        let from = self.players[idx].active.rotation;
-        let to = if cw { from.rotate_cw() } else { from.rotate_ccw() };
-        let kicks = KickTable::kicks(self.players[idx].active.piece, from, to);
-        for (_kick_idx, (dx, dy)) in kicks.iter().enumerate() {
-            let test = ActivePiece {
-                rotation: to,
-                x: self.players[idx].active.x + dx,
-                y: self.players[idx].active.y + dy,
-                ..self.players[idx].active.clone()
+        let player = &mut self.players[idx];
+        match kicked_rotation(&player.board, &player.active, cw, self.settings.kicks_enabled) {
+            Some((rotated, kick)) => {
+                player.active = rotated;
+                player.last_action_was_rotation = player.active.piece == Tetromino::T;
+                player.last_kick = kick;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Fewest key presses (taps, DAS to a wall, rotations, hard drop) that take a fresh
+    /// spawn of the active piece to where it is now, ignoring timing and the stack.
+    fn finesse_hint(&self, idx: usize) -> Result<FinesseHint, String> {
+        let player = self.players.get(idx).ok_or("invalid player index")?;
+        let inputs = self
+            .finesse_inputs(player, &player.active)
+            .ok_or("active piece position is not reachable from spawn")?;
+        let keys = inputs.len() as u32;
+        Ok(FinesseHint { inputs, keys })
+    }
+
+    /// A lock is a finesse fault when it took more shifts and rotations than the shortest
+    /// route from spawn. Drops don't count, and pieces that involved a hold are skipped.
+    fn is_finesse_fault(&self, idx: usize, locked: &ActivePiece, inputs: &[InputButton]) -> bool {
+        if inputs.contains(&InputButton::Hold) {
+            return false;
+        }
+        let moves = inputs
+            .iter()
+            .filter(|b| !matches!(b, InputButton::SoftDrop | InputButton::HardDrop))
+            .count();
+        if moves == 0 {
+            return false;
+        }
+        self.finesse_inputs(&self.players[idx], locked)
+            .is_some_and(|optimal| moves > optimal.len() - 1)
+    }
+
+    // Shortest input sequence, hard drop included, from a fresh spawn to `piece`'s footprint.
+    fn finesse_inputs(&self, player: &Player, piece: &ActivePiece) -> Option<Vec<String>> {
+        let board = Board::new();
+        let target = footprint(piece);
+        let shift = |piece: &ActivePiece, dx: i32| {
+            let moved = ActivePiece {
+                x: piece.x + dx,
+                ..piece.clone()
             };
-            if !self.players[idx].board.collision(&test) {
-                self.players[idx].active = test;
-                self.players[idx].last_action_was_rotation =
-                    self.players[idx].active.piece == Tetromino::T;
-                self.players[idx].last_kick = (*dx, *dy);
-                return true;
+            (!board.collision(&moved)).then_some(moved)
+        };
+        let das = |piece: &ActivePiece, dx: i32| {
+            let mut moved = shift(piece, dx)?;
+            while let Some(next) = shift(&moved, dx) {
+                moved = next;
+            }
+            Some(moved)
+        };
+        let kicks_enabled = self.settings.kicks_enabled;
+        let rotate =
+            |piece: &ActivePiece, cw: bool| kicked_rotation(&board, piece, cw, kicks_enabled).map(|(p, _)| p);
+        let apply = |piece: &ActivePiece, action: &str| match action {
+            "left" => shift(piece, -1),
+            "right" => shift(piece, 1),
+            "das_left" => das(piece, -1),
+            "das_right" => das(piece, 1),
+            "rotate_cw" => rotate(piece, true),
+            "rotate_ccw" => rotate(piece, false),
+            _ => rotate(&rotate(piece, true)?, true),
+        };
+        let actions = [
+            "left",
+            "right",
+            "das_left",
+            "das_right",
+            "rotate_cw",
+            "rotate_ccw",
+            "rotate_180",
+        ];
+
+        let start = player.new_piece(piece.piece);
+        let mut seen = vec![(start.x, start.rotation)];
+        let mut frontier = VecDeque::from([(start, Vec::new())]);
+        while let Some((piece, inputs)) = frontier.pop_front() {
+            if footprint(&piece) == target {
+                let mut inputs: Vec<String> = inputs;
+                inputs.push("hard_drop".to_string());
+                return Some(inputs);
+            }
+            for name in actions {
+                if let Some(next) = apply(&piece, name)
+                    && !seen.contains(&(next.x, next.rotation))
+                {
+                    seen.push((next.x, next.rotation));
+                    let mut path = inputs.clone();
+                    path.push(name.to_string());
+                    frontier.push_back((next, path));
+                }
             }
         }
-        false
+        None
     }
 
     fn try_hold(&mut self, idx: usize) {
@@ -1791,14 +3860,106 @@ impl Versus {
             return;
         }
         let current = self.players[idx].active.piece;
-        if let Some(held) = self.players[idx].hold {
-            self.players[idx].active = ActivePiece::new(held);
+        let player = &self.players[idx];
+        let Some(incoming) = player.hold.or_else(|| player.queue.first().copied()) else {
+            return;
+        };
+        let blocked = player.board.collision(&player.new_piece(incoming));
+        if blocked && self.settings.hold_top_out == HoldTopOutRule::Forbid {
+            return;
+        }
+        if let Some(held) = self.players[idx].hold {
+            self.players[idx].active = self.players[idx].new_piece(held);
             self.players[idx].hold = Some(current);
         } else {
             self.players[idx].hold = Some(current);
             self.players[idx].spawn_next();
         }
+        if blocked {
+            self.players[idx].top_out(TopOutReason::BlockOut);
+        }
         self.players[idx].held_on_turn = true;
+        self.stats[idx].holds = self.stats[idx].holds.saturating_add(1);
+        self.defensive_hold(idx);
+    }
+
+    fn garbage_rows(&self, idx: usize) -> Result<Vec<usize>, String> {
+        let player = self.players.get(idx).ok_or("invalid player index")?;
+        Ok(player.board.garbage_rows())
+    }
+
+    /// Every distinct resting spot (by occupied cells) of the active piece dropped straight
+    /// down from above, within the visible field, with the lines it would clear.
+    fn legal_placements(&self, idx: usize) -> Result<Vec<Placement>, String> {
+        let player = self.players.get(idx).ok_or("invalid player index")?;
+        let mut seen: Vec<Vec<(i32, i32)>> = Vec::new();
+        let mut placements = Vec::new();
+        for rotation in [Rotation::Spawn, Rotation::Right, Rotation::Reverse, Rotation::Left] {
+            let piece = ActivePiece { rotation, ..player.active.clone() };
+            let blocks = piece.blocks();
+            for x in -2..WIDTH as i32 + 2 {
+                let Some(y) = player.board.lowest_visible_drop_height(x, &blocks) else {
+                    continue;
+                };
+                let mut cells: Vec<(i32, i32)> =
+                    blocks.iter().map(|b| (x + b.x as i32, y + b.y as i32)).collect();
+                cells.sort_unstable();
+                if seen.contains(&cells) {
+                    continue;
+                }
+                seen.push(cells);
+                let mut board = player.board.clone();
+                board.lock_piece(x, y, &blocks, piece.color_id());
+                let mut lines = board.clear_lines();
+                if lines > 0 && self.settings.clear_gravity == ClearGravity::Cascade {
+                    lines += board.cascade().iter().sum::<usize>();
+                }
+                placements.push(Placement { x, y, rotation, lines });
+            }
+        }
+        Ok(placements)
+    }
+
+    /// Put the player back to just after their last line clear, with the piece that spawned
+    /// then, for retrying a downstack sequence. Undoes a top-out; stats keep counting. Only
+    /// human players keep a checkpoint.
+    fn undo_to_last_clear(&mut self, idx: usize) -> Result<(), String> {
+        let player = self.players.get_mut(idx).ok_or("invalid player index")?;
+        let checkpoint = player.last_clear.as_ref().ok_or("no line clear to return to")?;
+        player.board = checkpoint.board.clone();
+        player.active = checkpoint.active.clone();
+        player.queue = checkpoint.queue.clone();
+        player.forced_queue = checkpoint.forced_queue.clone();
+        player.randomizer = checkpoint.randomizer.clone_box();
+        player.hold = checkpoint.hold;
+        player.combo = checkpoint.combo;
+        player.back_to_back = checkpoint.back_to_back;
+        player.pending_garbage = checkpoint.pending_garbage.clone();
+        player.rising_garbage = checkpoint.rising_garbage.clone();
+        player.dealt_history = checkpoint.dealt_history.clone();
+        player.held_on_turn = false;
+        player.last_action_was_rotation = false;
+        player.garbage_delay_frames = 0;
+        player.topped_out = false;
+        player.top_out_reason = None;
+        self.top_out_ticks[idx] = None;
+        self.fall_accum[idx] = 0.0;
+        // Replaying the sequence should warn again, not stay silenced by the undone future.
+        self.in_danger[idx] = false;
+        self.drought_warned[idx].clear();
+        Ok(())
+    }
+
+    /// Under the defensive-hold rule, spending the turn's hold cancels some pending garbage.
+    fn defensive_hold(&mut self, idx: usize) {
+        let garbage = &self.settings.garbage;
+        if !garbage.defensive_hold || self.players[idx].pending_garbage.is_empty() {
+            return;
+        }
+        let lines = garbage.defensive_hold_lines;
+        let cancelled = lines - self.players[idx].cancel_pending_garbage(lines);
+        let stats = &mut self.stats[idx];
+        stats.garbage_cancelled = stats.garbage_cancelled.saturating_add(cancelled);
     }
 
     fn discard_piece(&mut self, idx: usize) {
@@ -1810,30 +3971,106 @@ impl Versus {
         player.back_to_back = false;
         player.last_action_was_rotation = false;
         // Apply any pending garbage now that the chain is broken.
-        if !player.pending_garbage.is_empty() {
-            let batches = std::mem::take(&mut player.pending_garbage);
-            let mut overflow = false;
-            for batch in batches {
-                if player.board.add_garbage(batch.lines, batch.hole) {
-                    overflow = true;
-                }
-            }
-            if overflow {
-                player.topped_out = true;
-                return;
-            }
+        self.land_pending_garbage(idx);
+        let player = &mut self.players[idx];
+        if player.topped_out {
+            return;
         }
         player.spawn_next();
         self.stats[idx].pieces = self.stats[idx].pieces.saturating_add(1);
         self.fall_accum[idx] = 0.0;
     }
 
+    /// Replace the visible field with `cells` (row-major, bottom row first, same as the
+    /// frame's `field`); rows above `height` and the buffer are cleared. With `reset_active`
+    /// the active piece is respawned, otherwise a field overlapping it is rejected.
+    fn set_field(
+        &mut self,
+        idx: usize,
+        cells: &[u8],
+        width: usize,
+        height: usize,
+        reset_active: bool,
+    ) -> Result<(), StateError> {
+        if width != WIDTH || height > VISIBLE_HEIGHT {
+            return Err(StateError::InvalidDimensions { width, height });
+        }
+        if cells.len() != width * height {
+            return Err(StateError::WrongLength {
+                expected: width * height,
+                got: cells.len(),
+            });
+        }
+        if let Some(&bad) = cells.iter().find(|&&c| c > 8) {
+            return Err(StateError::InvalidColor(bad));
+        }
+        let player = self.players.get_mut(idx).ok_or(StateError::InvalidPlayer)?;
+        let mut board = Board::new();
+        for (y, row) in cells.chunks(WIDTH).enumerate() {
+            board.cells[y].copy_from_slice(row);
+        }
+        let active = if reset_active {
+            player.new_piece(player.active.piece)
+        } else {
+            player.active.clone()
+        };
+        if board.collision(&active) {
+            return Err(if reset_active {
+                StateError::WouldTopOut
+            } else {
+                StateError::OverlapsActive
+            });
+        }
+        player.board = board;
+        player.active = active;
+        player.topped_out = false;
+        player.top_out_reason = None;
+        self.fall_accum[idx] = 0.0;
+        self.top_out_ticks[idx] = None;
+        Ok(())
+    }
+
+    /// Deal `pieces` next, ahead of the randomizer. With `replace_active`, the first of them
+    /// becomes the active piece.
+    fn set_queue(&mut self, idx: usize, pieces: &[u8], replace_active: bool) -> Result<(), StateError> {
+        let pieces = pieces
+            .iter()
+            .map(|&id| Tetromino::from_color_id(id).ok_or(StateError::InvalidPiece(id)))
+            .collect::<Result<VecDeque<_>, _>>()?;
+        let player = self.players.get_mut(idx).ok_or(StateError::InvalidPlayer)?;
+        if replace_active
+            && let Some(&first) = pieces.front()
+            && player.board.collision(&player.new_piece(first))
+        {
+            return Err(StateError::WouldTopOut);
+        }
+        player.forced_queue = pieces;
+        player.queue.clear();
+        if replace_active && let Some(first) = player.forced_queue.pop_front() {
+            player.active = player.new_piece(first);
+            player.held_on_turn = false;
+            player.last_action_was_rotation = false;
+            self.fall_accum[idx] = 0.0;
+        }
+        player.refill_queue();
+        Ok(())
+    }
+
+    /// Cells of `piece` (a color id) turned to `rotation`, as this player would see it,
+    /// relative to the rotation center like `hold_blocks`/`next_blocks`.
+    fn preview_blocks(&self, idx: usize, piece: u8, rotation: Rotation) -> Result<Vec<Point>, StateError> {
+        let player = self.players.get(idx).ok_or(StateError::InvalidPlayer)?;
+        let piece = Tetromino::from_color_id(piece).ok_or(StateError::InvalidPiece(piece))?;
+        let custom = player.custom_shape(piece).map(|s| &**s);
+        Ok(piece_blocks(piece, custom, rotation).to_vec())
+    }
+
     fn force_piece(&mut self, idx: usize, piece: Tetromino) {
         let player = &mut self.players[idx];
         if player.topped_out {
             return;
         }
-        player.active = ActivePiece::new(piece);
+        player.active = player.new_piece(piece);
         player.active.y = (VISIBLE_HEIGHT as i32) - 1;
         player.active.x = 4;
         player.held_on_turn = false;
@@ -1843,7 +4080,7 @@ impl Versus {
         self.fall_accum[idx] = 0.0;
     }
 
-    fn ghost(&self, idx: usize) -> Vec<Point> {
+    fn ghost_piece(&self, idx: usize) -> ActivePiece {
         let mut ghost = self.players[idx].active.clone();
         // Drop straight down until collision.
         loop {
@@ -1860,8 +4097,16 @@ impl Versus {
             }
         }
         ghost
-            .blocks()
+    }
+
+    /// Visible ghost cells in the current `ghost_style`.
+    fn ghost(&self, idx: usize) -> Vec<Point> {
+        let style = self.settings.effective_ghost_style();
+        let ghost = self.ghost_piece(idx);
+        let blocks = ghost.blocks();
+        blocks
             .iter()
+            .filter(|b| ghost_cell_shown(style, &blocks, **b))
             .filter_map(|b| {
                 let gy = ghost.y + b.y as i32;
                 if (0..VISIBLE_HEIGHT as i32).contains(&gy) {
@@ -1885,7 +4130,7 @@ impl Versus {
                     field.push(self.players[idx].cells(y, x));
                 }
             }
-            let active = self.players[idx]
+            let mut active = self.players[idx]
                 .active
                 .blocks()
                 .iter()
@@ -1901,70 +4146,216 @@ impl Versus {
                     }
                 })
                 .collect::<Vec<_>>();
-            let ghost = if self.settings.ghost_enabled {
-                self.ghost(idx)
-            } else {
-                Vec::new()
-            };
+            let mut ghost = self.ghost(idx);
+            let preview = self.settings.preview_count;
             let next = self.players[idx]
                 .queue
                 .iter()
-                .copied()
-                .map(|p| p.color_id())
+                .take(preview)
+                .map(|&p| self.players[idx].piece_color(p))
                 .collect();
-            let next_blocks = self.players[idx]
+            let mut next_blocks: Vec<Vec<Point>> = self.players[idx]
                 .queue
                 .iter()
-                .map(|p| spawn_blocks(*p).to_vec())
+                .take(preview)
+                .map(|&p| self.players[idx].spawn_blocks(p).to_vec())
                 .collect();
-            let hold_blocks = self.players[idx].hold.map(|p| spawn_blocks(p).to_vec());
-            let stats = &self.stats[idx];
-            let time_s = if stats.time_ms > 0.0 { stats.time_ms / 1000.0 } else { 0.0 };
-            let pps = if time_s > 0.0 {
-                stats.pieces as f32 / time_s
-            } else {
-                0.0
-            };
-            let kpp = if stats.pieces > 0 {
-                stats.keys as f32 / stats.pieces as f32
-            } else {
-                0.0
-            };
+            let mut hold_blocks = self.players[idx]
+                .hold
+                .map(|p| self.players[idx].spawn_blocks(p).to_vec());
+            if self.settings.mirror {
+                // The game itself runs unmirrored; only what the frontend sees is flipped.
+                for row in field.chunks_mut(WIDTH) {
+                    row.reverse();
+                }
+                for p in active.iter_mut().chain(ghost.iter_mut()) {
+                    p.x = WIDTH as i8 - 1 - p.x;
+                }
+                for p in next_blocks
+                    .iter_mut()
+                    .flatten()
+                    .chain(hold_blocks.iter_mut().flatten())
+                {
+                    p.x = -p.x;
+                }
+            }
+            let rotation = self.settings.display_rotation;
+            if rotation != 0 {
+                field = rotate_view_field(&field, rotation);
+                for p in active.iter_mut().chain(ghost.iter_mut()) {
+                    *p = rotate_view_point(*p, rotation);
+                }
+                for p in next_blocks
+                    .iter_mut()
+                    .flatten()
+                    .chain(hold_blocks.iter_mut().flatten())
+                {
+                    *p = rotate_view_offset(*p, rotation);
+                }
+            }
             players.push(PlayerView {
                 field,
+                changed: None,
                 active,
-                active_color: self.players[idx].active.piece.color_id(),
-                active_piece: self.players[idx].active.piece.color_id(),
+                active_color: self.players[idx].active.color_id(),
+                active_piece: self.players[idx].active.color_id(),
                 active_rotation: format!("{:?}", self.players[idx].active.rotation),
+                lock_progress: self.players[idx].lock_progress(),
                 ghost,
-                hold: self.players[idx].hold.map(|p| p.color_id()),
+                hold: self.players[idx].hold.map(|p| self.players[idx].piece_color(p)),
                 hold_blocks,
-                hold_color_id: self.players[idx].hold.map(|p| p.color_id()),
+                hold_color_id: self.players[idx].hold.map(|p| self.players[idx].piece_color(p)),
+                hold_available: !self.players[idx].held_on_turn && !self.players[idx].topped_out,
                 next,
                 next_blocks,
                 topped_out: self.players[idx].topped_out,
-                stats: PlayerStatsView {
-                    time_ms: stats.time_ms,
-                    pieces: stats.pieces,
-                    keys: stats.keys,
-                    attack: stats.attack,
-                    finesse: stats.finesse,
-                    pps,
-                    kpp,
-                    lines_sent: stats.lines_sent,
-                    pending_garbage: self.players[idx]
-                        .pending_garbage
-                        .iter()
-                        .map(|b| b.lines)
-                        .sum(),
-                },
+                combo: self.players[idx].combo,
+                combo_display: self.settings.combo.display(self.players[idx].combo),
+                stats: self.stats_view(idx),
                 summary: self.players[idx].recent_events.clone(),
             });
         }
         FrameView {
+            result: self.result(),
             players,
             settings: self.settings.clone(),
+            events: self.events.clone(),
+            controller_kind: (0..self.players.len()).map(|idx| self.controller_kind(idx)).collect(),
+            countdown_ms: self.countdown_ms,
+        }
+    }
+
+    fn stats_view(&self, idx: usize) -> PlayerStatsView {
+        let stats = &self.stats[idx];
+        PlayerStatsView {
+            time_ms: stats.time_ms,
+            active_play_ms: stats.active_play_ms,
+            pieces: stats.pieces,
+            keys: stats.keys,
+            attack: stats.attack,
+            finesse: stats.finesse,
+            pps: stats.pps(),
+            kpp: stats.kpp(),
+            lines_sent: stats.lines_sent,
+            garbage_cancelled: stats.garbage_cancelled,
+            garbage_sent: stats.garbage_sent,
+            pending_garbage: self.players[idx]
+                .pending_garbage
+                .iter()
+                .map(|b| b.lines)
+                .sum(),
+            garbage_received: stats.garbage_received,
+            net_garbage: stats.garbage_sent as i64 - stats.garbage_received as i64,
+        }
+    }
+
+    /// Low-rate view for spectators and stream overlays: fields, stats and garbage meters
+    /// only, without pieces, previews, settings or events.
+    fn broadcast_snapshot(&self) -> BroadcastView {
+        let players = (0..self.players.len())
+            .map(|idx| {
+                let player = &self.players[idx];
+                let stats = self.stats_view(idx);
+                let opponent = 1 - idx;
+                BroadcastPlayer {
+                    field: (0..VISIBLE_HEIGHT)
+                        .flat_map(|y| (0..WIDTH).map(move |x| player.cells(y, x)))
+                        .collect(),
+                    topped_out: player.topped_out,
+                    controller_kind: self.controller_kind(idx),
+                    attacked_by: (stats.pending_garbage > 0).then_some(opponent),
+                    rising_garbage: player.rising_garbage.len() as u32,
+                    stats,
+                }
+            })
+            .collect();
+        BroadcastView {
+            result: self.result(),
+            players,
+        }
+    }
+
+    fn controller_kind(&self, idx: usize) -> ControllerKind {
+        if self.drives_internal_bot(idx) {
+            ControllerKind::InternalBot
+        } else if !self.human[idx] || self.tbp_driven[idx] {
+            // Players without keyboard input only advance via TBP.
+            ControllerKind::ExternalBot
+        } else {
+            ControllerKind::Human
+        }
+    }
+
+    /// Same frame as `snapshot`, packed into bytes for the per-tick render path.
+    ///
+    /// Layout: `[PACKED_VERSION, player count]`, then per player:
+    /// flags (bit 0 topped out, bit 1 has hold), active piece id, active rotation, hold
+    /// piece id (0 = none); active then ghost cells as 4 `(x, y)` byte pairs each, `0xFF`
+    /// when hidden; pieces, keys, attack, lines sent and pending garbage as u32 LE;
+    /// time_ms, pps and kpp as f32 LE; preview length and that many piece ids; finally
+    /// the visible field, one byte per cell, bottom row first. Mirroring applies but
+    /// `display_rotation` does not; the packed field is always upright. Custom shapes are
+    /// cut or padded to four cells.
+    ///
+    /// Measured natively this is 1 allocation per frame against 26 for `snapshot` with empty
+    /// clear summaries (plus one per summary line, up to 20 per player), before
+    /// `serde_wasm_bindgen` turns that into several hundred JS values.
+    fn snapshot_packed(&self) -> Vec<u8> {
+        let preview = self.settings.preview_count;
+        let per_player = 4 + 16 + 32 + 1 + preview + WIDTH * VISIBLE_HEIGHT;
+        let mut out = Vec::with_capacity(2 + self.players.len() * per_player);
+        out.push(PACKED_VERSION);
+        out.push(self.players.len() as u8);
+        let flip = |x: i32| {
+            if self.settings.mirror {
+                WIDTH as i32 - 1 - x
+            } else {
+                x
+            }
+        };
+        for (idx, player) in self.players.iter().enumerate() {
+            let stats = &self.stats[idx];
+            out.push(player.topped_out as u8 | (player.hold.is_some() as u8) << 1);
+            out.push(player.active.color_id());
+            out.push(player.active.rotation as u8);
+            out.push(player.hold.map_or(0, |p| player.piece_color(p)));
+
+            let ghost = self.ghost_piece(idx);
+            let ghost_style = self.settings.effective_ghost_style();
+            for (piece, style) in [(&player.active, GhostStyle::Full), (&ghost, ghost_style)] {
+                let blocks = piece.blocks();
+                for i in 0..4 {
+                    // Missing cells of a small custom shape pack as hidden.
+                    let b = blocks.get(i).copied().unwrap_or(Point { x: 0, y: -128 });
+                    let y = piece.y + b.y as i32;
+                    if ghost_cell_shown(style, &blocks, b) && (0..VISIBLE_HEIGHT as i32).contains(&y) {
+                        out.push(flip(piece.x + b.x as i32) as u8);
+                        out.push(y as u8);
+                    } else {
+                        out.extend_from_slice(&[0xFF, 0xFF]);
+                    }
+                }
+            }
+
+            let pending: u32 = player.pending_garbage.iter().map(|b| b.lines).sum();
+            for v in [stats.pieces, stats.keys, stats.attack, stats.lines_sent, pending] {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            for v in [stats.time_ms, stats.pps(), stats.kpp()] {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+
+            let next = &player.queue[..player.queue.len().min(preview)];
+            out.push(next.len() as u8);
+            out.extend(next.iter().map(|&p| player.piece_color(p)));
+
+            for y in 0..VISIBLE_HEIGHT {
+                for x in 0..WIDTH {
+                    out.push(player.cells(y, flip(x as i32) as usize));
+                }
+            }
         }
+        out
     }
 
     fn tbp_start(&self, idx: usize) -> Result<frontend_msg::Start, String> {
@@ -2014,15 +4405,10 @@ impl Versus {
         Ok(start)
     }
 
-    fn apply_tbp_move(
-        &mut self,
-        idx: usize,
-        mv: tbp_data::Move,
-    ) -> Result<AppliedMoveResult, String> {
-        if idx >= self.players.len() {
-            return Err("invalid player index".into());
-        }
-        if self.players[idx].topped_out {
+    // Work out where a TBP move puts its piece without touching the player, so the
+    // same rules back both applying and previewing moves.
+    fn plan_tbp_move(player: &Player, mv: &tbp_data::Move, strict: bool) -> Result<TbpPlacement, String> {
+        if player.topped_out {
             return Err("player topped out".into());
         }
         let desired_piece: Tetromino = mv
@@ -2032,88 +4418,102 @@ impl Versus {
             .known()
             .ok_or("unknown piece in move")?
             .into();
-        {
-            let player = &mut self.players[idx];
-            if desired_piece != player.active.piece {
-                let queue_front = player.queue.get(0).copied();
-                if let Some(hold) = player.hold {
-                    if hold == desired_piece {
-                        let previous = player.active.piece;
-                        player.active = ActivePiece::new(desired_piece);
-                        player.hold = Some(previous);
-                        player.held_on_turn = true;
-                    } else if queue_front == Some(desired_piece) && !player.held_on_turn {
-                        // Bot used hold to skip to the next piece.
-                        player.hold = Some(player.active.piece);
-                        player.active = ActivePiece::new(desired_piece);
-                        player.queue.remove(0);
-                        player.refill_queue();
-                        player.held_on_turn = true;
-                    } else {
-                        return Err("move piece not available (not current or held)".into());
-                    }
+        let mut hold = TbpHold::None;
+        if desired_piece != player.active.piece {
+            let queue_front = player.queue.first().copied();
+            if let Some(held) = player.hold {
+                if held == desired_piece {
+                    hold = TbpHold::Swap;
                 } else if queue_front == Some(desired_piece) && !player.held_on_turn {
-                    // Hold was empty; bot is effectively holding current and using next.
-                    player.hold = Some(player.active.piece);
-                    player.active = ActivePiece::new(desired_piece);
-                    player.queue.remove(0);
-                    player.refill_queue();
-                    player.held_on_turn = true;
+                    // Bot used hold to skip to the next piece.
+                    hold = TbpHold::TakeNext;
                 } else {
-                    return Err("move piece not available (hold empty)".into());
+                    return Err("move piece not available (not current or held)".into());
                 }
+            } else if queue_front == Some(desired_piece) && !player.held_on_turn {
+                // Hold was empty; bot is effectively holding current and using next.
+                hold = TbpHold::TakeNext;
+            } else {
+                return Err("move piece not available (hold empty)".into());
             }
+        }
 
-            let orientation = mv
-                .location
-                .orientation
-                .clone()
-                .known()
-                .ok_or("unknown orientation in move")?;
-            player.active.rotation = from_tbp_orientation(orientation);
-            player.active.x = mv.location.x as i32;
-            player.active.y = mv.location.y as i32;
-            if player.active.piece == Tetromino::I
-                && (player.active.rotation == Rotation::Right
-                    || player.active.rotation == Rotation::Reverse)
-            {
-                // Our I vertical column is shifted +1 relative to TBP coords; align to TBP pivot.
-                player.active.x -= 1;
-            }
-            if desired_piece == Tetromino::T {
-                if let Some(spin) = mv.spin.clone().known() {
-                    match spin {
-                        tbp_data::Spin::None => {}
-                        tbp_data::Spin::Mini | tbp_data::Spin::Full => {
-                            player.last_action_was_rotation = true;
-                            player.last_kick = (2, 1); // treat as a kicked rotation to satisfy mini rule if needed
-                        }
-                        _ => {}
-                    }
-                }
+        let mut active = if hold == TbpHold::None {
+            player.active.clone()
+        } else {
+            player.new_piece(desired_piece)
+        };
+        let orientation = mv
+            .location
+            .orientation
+            .clone()
+            .known()
+            .ok_or("unknown orientation in move")?;
+        active.rotation = from_tbp_orientation(orientation);
+        active.x = mv.location.x;
+        active.y = mv.location.y;
+        if active.piece == Tetromino::I
+            && (active.rotation == Rotation::Right || active.rotation == Rotation::Reverse)
+        {
+            // Our I vertical column is shifted +1 relative to TBP coords; align to TBP pivot.
+            active.x -= 1;
+        }
+        let spin = desired_piece == Tetromino::T
+            && matches!(
+                mv.spin.clone().known(),
+                Some(tbp_data::Spin::Mini | tbp_data::Spin::Full)
+            );
+        if player.board.collision(&active) {
+            if strict {
+                return Err(format!("Collision: placement at y={} overlaps the board", mv.location.y));
             }
-            if player.board.collision(&player.active) {
-                // If the suggested y collides, try dropping to the lowest legal height for this x/rotation.
-                let shape = player.active.blocks();
-                if let Some(drop_y) = player.board.lowest_drop_height(player.active.x, &shape) {
-                    player.active.y = drop_y;
-                    if player.board.collision(&player.active) {
-                        return Err("placement collides with board".into());
-                    }
-                } else {
+            // If the suggested y collides, try dropping to the lowest legal height for this x/rotation.
+            let shape = active.blocks();
+            if let Some(drop_y) = player.board.lowest_drop_height(active.x, &shape) {
+                active.y = drop_y;
+                if player.board.collision(&active) {
                     return Err("placement collides with board".into());
                 }
+            } else {
+                return Err("placement collides with board".into());
             }
         }
+        Ok(TbpPlacement { active, hold, spin })
+    }
 
-        let (cleared, t_spin);
-        {
-            let player = &mut self.players[idx];
-            let res = player.lock_piece();
-            cleared = res.0;
-            t_spin = res.1;
+    fn apply_tbp_move(
+        &mut self,
+        idx: usize,
+        mv: tbp_data::Move,
+    ) -> Result<AppliedMoveResult, String> {
+        let player = self.players.get(idx).ok_or("invalid player index")?;
+        let placement = Self::plan_tbp_move(player, &mv, self.strict_tbp)?;
+        self.tbp_driven[idx] = true;
+        let hold = placement.hold;
+        self.players[idx].take_tbp_placement(placement);
+
+        if hold != TbpHold::None {
+            self.stats[idx].holds = self.stats[idx].holds.saturating_add(1);
+            self.defensive_hold(idx);
         }
+
+        let (cleared, t_spin, _) = self.players[idx].lock_piece(&self.settings);
         self.on_piece_locked(idx, cleared, t_spin, false);
+        Ok(self.locked_result(idx, cleared, hold != TbpHold::None))
+    }
+
+    /// Lock the active piece where it is, floating or not, as a "lock in place" control.
+    fn force_lock(&mut self, idx: usize) -> Result<AppliedMoveResult, String> {
+        let player = self.players.get_mut(idx).ok_or("invalid player index")?;
+        if player.topped_out {
+            return Err("player topped out".into());
+        }
+        let (cleared, t_spin, overflow) = player.lock_piece(&self.settings);
+        self.on_piece_locked(idx, cleared, t_spin, overflow);
+        Ok(self.locked_result(idx, cleared, false))
+    }
+
+    fn locked_result(&mut self, idx: usize, cleared: usize, inferred_hold: bool) -> AppliedMoveResult {
         self.fall_accum[idx] = 0.0;
 
         let (topped_out, active_piece, new_queue_piece, combo, back_to_back) = {
@@ -2134,16 +4534,199 @@ impl Versus {
             )
         };
 
-        Ok(AppliedMoveResult {
+        AppliedMoveResult {
             lines_cleared: cleared,
             topped_out,
             active_piece,
             new_queue_piece,
             combo,
             back_to_back,
+            inferred_hold,
+        }
+    }
+
+    /// Apply `moves` in order, stopping at the first one that can't be applied.
+    fn replay_tbp_moves(&mut self, idx: usize, moves: Vec<tbp_data::Move>) -> Result<ReplayReport, String> {
+        if idx >= self.players.len() {
+            return Err("invalid player index".into());
+        }
+        let mut results = Vec::with_capacity(moves.len());
+        let (mut failed_at, mut reason) = (None, None);
+        for (i, mv) in moves.into_iter().enumerate() {
+            let applied = if self.players[idx].topped_out {
+                Err("player topped out".to_string())
+            } else {
+                self.apply_tbp_move(idx, mv)
+            };
+            match applied {
+                Ok(result) => results.push(result),
+                Err(e) => {
+                    failed_at = Some(i);
+                    reason = Some(e);
+                    break;
+                }
+            }
+        }
+        Ok(ReplayReport {
+            results,
+            failed_at,
+            reason,
+            board_hash: self.players[idx].board.hash_hex(),
+        })
+    }
+
+    fn preview_tbp_move(&self, idx: usize, mv: &tbp_data::Move) -> Result<MovePreview, String> {
+        let player = self.players.get(idx).ok_or("invalid player index")?;
+        let placement = match Self::plan_tbp_move(player, mv, self.strict_tbp) {
+            Ok(p) => p,
+            Err(reason) => {
+                return Ok(MovePreview {
+                    legal: false,
+                    lines: 0,
+                    spin: false,
+                    resulting_hash: player.board.hash_hex(),
+                    reason: Some(reason),
+                });
+            }
+        };
+        // The same lock `apply_tbp_move` does, on a throwaway copy of the player.
+        let mut scratch = player.clone();
+        scratch.take_tbp_placement(placement);
+        let (lines, spin, _) = scratch.lock_piece(&self.settings);
+        let reason = scratch
+            .top_out_reason
+            .filter(|_| !player.topped_out)
+            .map(|reason| format!("Tops out: {:?}", reason));
+        Ok(MovePreview {
+            legal: reason.is_none(),
+            lines,
+            spin,
+            resulting_hash: scratch.board.hash_hex(),
+            reason,
+        })
+    }
+
+    fn set_gravity_ms(&mut self, ms_per_row: f32) -> Result<(), String> {
+        if !(ms_per_row.is_finite() && ms_per_row > 0.0) {
+            return Err("gravity must be a positive number of ms per row".into());
+        }
+        self.gravity_ms = ms_per_row;
+        Ok(())
+    }
+
+    /// Set gravity in G (rows per frame at 60 fps): 1G falls a row every 1000 / 60 ms, so
+    /// `gravity_ms = 1000 / (60 * g)`. 20G is ~0.83 ms per row, i.e. instant at any frame rate.
+    fn set_gravity_g(&mut self, g: f32) -> Result<(), String> {
+        if !(g.is_finite() && g > 0.0) {
+            return Err("gravity G must be positive".into());
+        }
+        self.set_gravity_ms(1000.0 / (60.0 * g))
+    }
+
+    /// Milliseconds until the active piece locks, or -1 while it is airborne.
+    fn lock_timer(&self, idx: usize) -> Result<f32, String> {
+        let player = self.players.get(idx).ok_or("invalid player index")?;
+        if player.on_ground() {
+            Ok(player.active.lock_timer.max(0.0))
+        } else {
+            Ok(-1.0)
+        }
+    }
+
+    fn move_resets_remaining(&self, idx: usize) -> Result<u8, String> {
+        let player = self.players.get(idx).ok_or("invalid player index")?;
+        Ok(player.active.move_resets)
+    }
+
+    // Pieces left in the current bag as sorted color ids, so the deal order isn't leaked.
+    fn bag_state(&self, idx: usize) -> Result<Vec<u8>, String> {
+        let player = self.players.get(idx).ok_or("invalid player index")?;
+        let mut remaining: Vec<u8> = player
+            .randomizer
+            .bag_state()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| p.color_id())
+            .collect();
+        remaining.sort_unstable();
+        Ok(remaining)
+    }
+
+    /// Upcoming pieces that are already certain, as color ids in deal order: the whole
+    /// dealt queue (shown or not), any forced pieces, then the last piece of a bag when only
+    /// one is left in it.
+    fn guaranteed_next(&self, idx: usize) -> Result<Vec<u8>, String> {
+        let player = self.players.get(idx).ok_or("invalid player index")?;
+        let mut known: Vec<Tetromino> =
+            player.queue.iter().chain(&player.forced_queue).copied().collect();
+        if player.forced_queue.is_empty()
+            && let Some(bag) = player.randomizer.bag_state()
+            && let [last] = bag[..]
+        {
+            known.push(last);
+        }
+        Ok(known.into_iter().map(|p| player.piece_color(p)).collect())
+    }
+
+    fn detailed_stats(&self, idx: usize) -> Result<DetailedStats, String> {
+        let player = self.players.get(idx).ok_or("invalid player index")?;
+        let droughts: Vec<PieceDrought> = Tetromino::all()
+            .into_iter()
+            .map(|piece| PieceDrought {
+                piece,
+                pieces_since: player.drought(piece),
+            })
+            .collect();
+        Ok(DetailedStats {
+            holds: self.stats[idx].holds,
+            i_drought: player.drought(Tetromino::I),
+            longest_drought: droughts.iter().map(|d| d.pieces_since).max().unwrap_or(0),
+            droughts,
+            lines_per_piece: self.stats[idx].lines_per_piece(),
+            longest_dry_streak: self.stats[idx].longest_dry_streak,
         })
     }
 
+    fn final_results(&self) -> Vec<FinalResults> {
+        self.players
+            .iter()
+            .zip(&self.stats)
+            .map(|(player, stats)| FinalResults {
+                time_ms: stats.time_ms,
+                active_play_ms: stats.active_play_ms,
+                pieces: stats.pieces,
+                pps: stats.pps(),
+                kpp: stats.kpp(),
+                apm: stats.apm(),
+                attack: stats.attack,
+                lines_sent: stats.lines_sent,
+                max_combo: stats.max_combo,
+                tetrises: stats.tetrises,
+                t_spins: stats.t_spins,
+                perfect_clears: stats.perfect_clears,
+                finesse_faults: stats.finesse,
+                top_out: player.top_out_reason,
+            })
+            .collect()
+    }
+
+    fn set_input_delay(&mut self, idx: usize, ticks: usize) -> Result<(), String> {
+        let ctrl = self.controllers.get_mut(idx).ok_or("invalid player index")?;
+        ctrl.input_delay = ticks;
+        Ok(())
+    }
+
+    fn set_player_paused(&mut self, idx: usize, paused: bool) -> Result<(), String> {
+        let was_paused = self.paused.get(idx).copied().ok_or("invalid player index")?;
+        self.paused[idx] = paused;
+        if was_paused && !paused {
+            // Resume with a fresh lock delay so a piece paused on the ground can't lock at once.
+            self.players[idx].active.lock_timer = LOCK_DELAY_MS;
+            self.fall_accum[idx] = 0.0;
+        }
+        Ok(())
+    }
+
     fn set_randomizer(&mut self, player: usize, kind: RandomizerKind) {
         if let Some(p) = self.players.get_mut(player) {
             p.set_randomizer(kind);
@@ -2159,34 +4742,38 @@ impl Player {
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct AttackTable {
-    pub _0_lines: u8,
-    pub _1_line_single: u8,
-    pub _2_lines_double: u8,
-    pub _3_lines_triple: u8,
-    pub _4_lines: u8,
-    pub t_spin_double: u8,
-    pub t_spin_triple: u8,
-    pub t_spin_single: u8,
-    pub t_spin_mini_single: u8,
-    pub perfect_clear: u8,
-    pub back_to_back_bonus: u8,
+    pub _0_lines: u16,
+    pub _1_line_single: u16,
+    pub _2_lines_double: u16,
+    pub _3_lines_triple: u16,
+    pub _4_lines: u16,
+    pub t_spin_double: u16,
+    pub t_spin_triple: u16,
+    pub t_spin_single: u16,
+    pub t_spin_mini_single: u16,
+    #[serde(default = "default_t_spin_mini_double")]
+    pub t_spin_mini_double: u16,
+    #[serde(default = "default_t_spin_mini_triple")]
+    pub t_spin_mini_triple: u16,
+    pub perfect_clear: u16,
+    pub back_to_back_bonus: u16,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ComboTable {
-    pub c0: u8,
-    pub c1: u8,
-    pub c2: u8,
-    pub c3: u8,
-    pub c4: u8,
-    pub c5: u8,
-    pub c6: u8,
-    pub c7: u8,
-    pub c8: u8,
-    pub c9: u8,
-    pub c10: u8,
-    pub c11: u8,
-    pub c12_plus: u8,
+    pub c0: u16,
+    pub c1: u16,
+    pub c2: u16,
+    pub c3: u16,
+    pub c4: u16,
+    pub c5: u16,
+    pub c6: u16,
+    pub c7: u16,
+    pub c8: u16,
+    pub c9: u16,
+    pub c10: u16,
+    pub c11: u16,
+    pub c12_plus: u16,
 }
 
 fn default_attack_table() -> AttackTable {
@@ -2200,11 +4787,22 @@ fn default_attack_table() -> AttackTable {
         t_spin_triple: 6,      // send 6 lines
         t_spin_single: 2,      // send 2 lines
         t_spin_mini_single: 0, // unchanged
+        t_spin_mini_double: 1, // well under a full T-spin double, never below a plain double
+        t_spin_mini_triple: 2, // well under a full T-spin triple, never below a plain triple
         perfect_clear: 10,
         back_to_back_bonus: 1,
     }
 }
 
+// Tables saved before the mini double/triple entries existed load with the defaults.
+fn default_t_spin_mini_double() -> u16 {
+    default_attack_table().t_spin_mini_double
+}
+
+fn default_t_spin_mini_triple() -> u16 {
+    default_attack_table().t_spin_mini_triple
+}
+
 fn default_combo_table() -> ComboTable {
     ComboTable {
         c0: 0,
@@ -2232,6 +4830,23 @@ mod tests {
         pts
     }
 
+    fn single_piece_versus(piece: Tetromino) -> Versus {
+        let kind = RandomizerKind::SinglePiece { piece };
+        Versus::new(GameSettings::default(), BotConfig::default(), [kind.clone(), kind])
+    }
+
+    fn tbp_move(piece: Tetromino, orientation: tbp_data::Orientation, x: i32, y: i32) -> tbp_data::Move {
+        tbp_data::Move::new(
+            tbp_data::PieceLocation::new(
+                MaybeUnknown::Known(piece.into()),
+                MaybeUnknown::Known(orientation),
+                x,
+                y,
+            ),
+            MaybeUnknown::Known(tbp_data::Spin::None),
+        )
+    }
+
     #[test]
     fn srs_shapes_match_reference() {
         let expected = |piece, pts: &[(i8, i8)]| {
@@ -2263,60 +4878,1856 @@ mod tests {
         let kicks_i_back = KickTable::kicks(Tetromino::I, Rotation::Right, Rotation::Spawn);
         assert_eq!(kicks_i_back, vec![(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]);
     }
-}
-
-#[wasm_bindgen]
-pub struct GameClient {
-    versus: Versus,
-    input_state: InputState,
-}
 
-#[wasm_bindgen]
-impl GameClient {
-    #[wasm_bindgen(constructor)]
-    pub fn new(settings: JsValue, bot_pps: f32, randomizers: JsValue) -> Result<GameClient, JsValue> {
-        let settings: GameSettings = from_value(settings).unwrap_or_default();
-        let randomizers: [RandomizerKind; 2] = from_value(randomizers)
-            .unwrap_or([RandomizerKind::SevenBag, RandomizerKind::SevenBag]);
-        let versus = Versus::new(settings, BotConfig { pps: bot_pps }, randomizers);
-        Ok(Self {
-            versus,
-            input_state: InputState::default(),
-        })
+    #[test]
+    fn spawn_on_stack_gets_one_frame_grace() {
+        let mut vs = single_piece_versus(Tetromino::I);
+        // Stack directly under the spawn row so the I is grounded the moment it appears.
+        for x in 3..=6 {
+            vs.players[0].board.cells[VISIBLE_HEIGHT - 2][x] = 8;
+        }
+        vs.tick(LOCK_DELAY_MS, InputFrame::default());
+        assert_eq!(vs.stats[0].pieces, 0, "spawn frame must not lock");
+        vs.tick(LOCK_DELAY_MS, InputFrame::default());
+        assert_eq!(vs.stats[0].pieces, 1, "lock delay resumes after the spawn frame");
     }
 
-    #[wasm_bindgen(js_name = tick)]
-    pub fn tick(&mut self, dt_ms: f32) -> Result<JsValue, JsValue> {
-        let frame: InputFrame = self.input_state.clone().into();
-        self.versus.tick(dt_ms, frame);
-        to_value(&self.versus.snapshot()).map_err(|e| e.into())
+    #[test]
+    fn input_delay_holds_edges_until_applied() {
+        let mut vs = single_piece_versus(Tetromino::I);
+        vs.set_input_delay(0, 2).unwrap();
+        let press = InputFrame {
+            hard_drop: true,
+            ..InputFrame::default()
+        };
+        vs.tick(16.0, press);
+        vs.tick(16.0, InputFrame::default());
+        assert_eq!(vs.stats[0].pieces, 0);
+        vs.tick(16.0, InputFrame::default());
+        assert_eq!(vs.stats[0].pieces, 1, "delayed hard drop still fires once");
+        vs.tick(16.0, InputFrame::default());
+        assert_eq!(vs.stats[0].pieces, 1);
+    }
+
+    #[test]
+    fn lowering_input_delay_keeps_queued_edges() {
+        let mut vs = single_piece_versus(Tetromino::I);
+        vs.set_input_delay(0, 4).unwrap();
+        let press = InputFrame {
+            hard_drop: true,
+            ..InputFrame::default()
+        };
+        for frame in [press, InputFrame::default(), press, InputFrame::default()] {
+            vs.tick(16.0, frame);
+        }
+        assert_eq!(vs.stats[0].pieces, 0);
+        vs.set_input_delay(0, 0).unwrap();
+        for _ in 0..4 {
+            vs.tick(16.0, InputFrame::default());
+        }
+        assert_eq!(vs.stats[0].pieces, 2, "both queued hard drops fire");
+        assert!(vs.controllers[0].delayed.is_empty(), "the queue catches up");
+    }
+
+    #[test]
+    fn held_hold_key_fires_once() {
+        let mut vs = single_piece_versus(Tetromino::T);
+        let hold = InputFrame {
+            hold: true,
+            ..InputFrame::default()
+        };
+        for _ in 0..4 {
+            vs.tick(16.0, hold);
+        }
+        assert!(!vs.snapshot().players[0].hold_available);
+        // Keep the key down through a lock so the fresh piece's hold isn't spent.
+        vs.tick(16.0, InputFrame { hard_drop: true, ..hold });
+        for _ in 0..4 {
+            vs.tick(16.0, hold);
+        }
+        assert_eq!(vs.stats[0].pieces, 1);
+        assert_eq!(vs.stats[0].holds, 1);
+        assert_eq!(vs.stats[0].keys, 2, "one hold edge plus the hard drop");
+        assert!(vs.snapshot().players[0].hold_available);
+    }
+
+    #[test]
+    fn mirrored_s_clears_like_z() {
+        let mut plain = single_piece_versus(Tetromino::Z);
+        let mut mirrored = single_piece_versus(Tetromino::S);
+        mirrored.settings.mirror = true;
+
+        // Fill the bottom row except under the Z's lower half, plus a marker cell above.
+        let landing = plain.ghost(0);
+        plain.players[0].board.cells[0] = [8; WIDTH];
+        for p in landing.iter().filter(|p| p.y == 0) {
+            plain.players[0].board.cells[0][p.x as usize] = 0;
+        }
+        plain.players[0].board.cells[1][0] = 8;
+        for y in 0..2 {
+            let mut row = plain.players[0].board.cells[y];
+            row.reverse();
+            mirrored.players[0].board.cells[y] = row;
+        }
+
+        // One tap left on the mirrored board lines the S up with the Z's footprint.
+        mirrored.tick(16.0, InputFrame { left: true, ..InputFrame::default() });
+        mirrored.tick(16.0, InputFrame::default());
+        let [mirrored_ghost, plain_ghost] = [mirrored.snapshot(), plain.snapshot()].map(|v| {
+            let mut g = v.players[0].ghost.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>();
+            g.sort();
+            g
+        });
+        assert_eq!(mirrored_ghost, plain_ghost);
+
+        let drop = InputFrame {
+            hard_drop: true,
+            ..InputFrame::default()
+        };
+        plain.tick(16.0, drop);
+        mirrored.tick(16.0, drop);
+        // Colors differ (S vs Z) but the occupied cells must match exactly.
+        let [a, b] = [plain.snapshot(), mirrored.snapshot()]
+            .map(|v| v.players[0].field.iter().map(|&c| c != 0).collect::<Vec<_>>());
+        assert_eq!(a, b);
+        // The bottom row cleared: the marker and the Z's top half are all that's left.
+        assert!(a[0]);
+        assert_eq!(a.iter().filter(|&&c| c).count(), 3);
+    }
+
+    #[test]
+    fn landed_garbage_rises_one_row_per_interval() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.settings.garbage.rise_interval_ms = 100.0;
+        vs.settings.garbage.fixed_hole = Some(0);
+        vs.queue_garbage(0, 2);
+        vs.tick(16.0, InputFrame { hard_drop: true, ..InputFrame::default() });
+        assert_eq!(vs.players[0].rising_garbage.len(), 2);
+        assert_eq!(vs.players[0].board.cells[0][4], Tetromino::O.color_id());
+
+        vs.events.clear();
+        vs.tick(60.0, InputFrame::default());
+        assert_eq!(vs.players[0].rising_garbage.len(), 2);
+        vs.tick(40.0, InputFrame::default());
+        assert_eq!(vs.players[0].board.cells[0][4], 8, "first row rose");
+        assert_eq!(vs.players[0].board.cells[1][4], Tetromino::O.color_id());
+        assert!(matches!(
+            vs.events.as_slice(),
+            [GameEvent::GarbageRising { player: 0, hole: 0, remaining: 1 }]
+        ));
+        vs.tick(100.0, InputFrame::default());
+        assert!(vs.players[0].rising_garbage.is_empty());
+        assert_eq!(vs.players[0].board.cells[1][4], 8);
+        assert_eq!(vs.players[0].board.cells[1][0], 0);
+    }
+
+    #[test]
+    fn packed_snapshot_matches_frame_view() {
+        let mut vs = single_piece_versus(Tetromino::T);
+        vs.players[0].board.cells[0][0] = 8;
+        vs.players[0].hold = Some(Tetromino::I);
+        let view = vs.snapshot();
+        let packed = vs.snapshot_packed();
+        assert_eq!(packed[..2], [PACKED_VERSION, 2]);
+
+        let p0 = &packed[2..];
+        assert_eq!(p0[0], 0b10, "has hold, not topped out");
+        assert_eq!(p0[1], view.players[0].active_piece);
+        assert_eq!(p0[3], Tetromino::I.color_id());
+        let ghost: Vec<(u8, u8)> = p0[12..20].chunks(2).map(|c| (c[0], c[1])).collect();
+        let expected: Vec<(u8, u8)> =
+            view.players[0].ghost.iter().map(|p| (p.x as u8, p.y as u8)).collect();
+        assert_eq!(ghost, expected);
+        let preview_at = 4 + 16 + 32;
+        let preview = p0[preview_at] as usize;
+        assert_eq!(p0[preview_at + 1..][..preview], view.players[0].next[..]);
+        let field_at = preview_at + 1 + preview;
+        assert_eq!(p0[field_at..][..WIDTH * VISIBLE_HEIGHT], view.players[0].field[..]);
+    }
+
+    #[test]
+    fn forced_queue_is_dealt_before_randomizer() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        use Tetromino::*;
+        let ids: Vec<u8> = [T, I, S, Z, L, J, T, I].iter().map(|p| p.color_id()).collect();
+        assert_eq!(vs.set_queue(0, &[9], false), Err(StateError::InvalidPiece(9)));
+        vs.set_queue(0, &ids, true).unwrap();
+        assert_eq!(vs.players[0].active.piece, Tetromino::T);
+        let start = vs.tbp_start(0).unwrap();
+        assert_eq!(start.queue.len(), 7);
+        // Drain past the forced pieces; the single-piece randomizer takes over after.
+        for expected in &ids[1..] {
+            assert_eq!(vs.players[0].queue[0].color_id(), *expected);
+            vs.players[0].spawn_next();
+        }
+        assert!(vs.players[0].queue.iter().all(|p| *p == Tetromino::O));
+    }
+
+    #[test]
+    fn finesse_hint_finds_minimal_inputs() {
+        let mut vs = single_piece_versus(Tetromino::T);
+        let hint = vs.finesse_hint(0).unwrap();
+        assert_eq!(hint.inputs, ["hard_drop"]);
+
+        vs.players[0].active.x = 0;
+        vs.players[0].active.rotation = Rotation::Right;
+        let hint = vs.finesse_hint(0).unwrap();
+        assert_eq!(hint.keys, 3);
+        assert_eq!(hint.inputs, ["rotate_cw", "das_left", "hard_drop"]);
+    }
+
+    #[test]
+    fn extra_presses_count_as_finesse_faults() {
+        let tap = |vs: &mut Versus, input: InputFrame| {
+            vs.tick(1.0, input);
+            vs.tick(1.0, InputFrame::default());
+        };
+        let left = InputFrame { left: true, ..InputFrame::default() };
+        let right = InputFrame { right: true, ..InputFrame::default() };
+        let drop = InputFrame { hard_drop: true, ..InputFrame::default() };
+        let mut vs = single_piece_versus(Tetromino::T);
+        tap(&mut vs, left);
+        tap(&mut vs, drop);
+        assert_eq!((vs.stats[0].pieces, vs.stats[0].finesse), (1, 0));
+
+        // Left, right, left ends where a single tap would have.
+        tap(&mut vs, left);
+        tap(&mut vs, right);
+        tap(&mut vs, left);
+        tap(&mut vs, drop);
+        assert_eq!((vs.stats[0].pieces, vs.stats[0].finesse), (2, 1));
+    }
+
+    #[test]
+    fn held_soft_drop_on_floor_waits_for_lock_delay() {
+        let soft = InputFrame {
+            soft_drop: true,
+            ..InputFrame::default()
+        };
+        for soft_drop_lock in [false, true] {
+            let mut vs = single_piece_versus(Tetromino::T);
+            vs.settings.soft_drop_lock = soft_drop_lock;
+            vs.players[0].active = vs.ghost_piece(0);
+            // Spawn frame is grace; lock delay counts from the next tick.
+            vs.tick(100.0, soft);
+            assert!(matches!(vs.events[..], [GameEvent::SoftLockPending { player: 0, .. }]));
+            vs.tick(100.0, soft);
+            if soft_drop_lock {
+                assert_eq!(vs.stats[0].pieces, 1, "soft_drop_lock locks right away");
+                continue;
+            }
+            for _ in 0..3 {
+                vs.tick(100.0, soft);
+            }
+            assert_eq!(vs.stats[0].pieces, 0, "no early lock while soft dropping");
+            vs.tick(100.0, soft);
+            assert_eq!(vs.stats[0].pieces, 1);
+            assert_eq!(vs.events.len(), 1, "pending is reported once per piece");
+        }
+    }
+
+    #[test]
+    fn display_rotation_only_transforms_the_view() {
+        let mut vs = single_piece_versus(Tetromino::L);
+        vs.players[0].board.cells[0][1] = 8;
+        let upright = vs.snapshot();
+        for (rotation, marker) in [(90, (0, 8)), (180, (8, 19)), (270, (19, 1))] {
+            vs.settings.display_rotation = rotation;
+            let view = vs.snapshot();
+            let width = if rotation == 180 { WIDTH } else { VISIBLE_HEIGHT };
+            assert_eq!(view.players[0].field[marker.1 * width + marker.0], 8);
+            assert_eq!(view.players[0].field.iter().filter(|&&c| c != 0).count(), 1);
+            let expected: Vec<Point> = upright.players[0]
+                .active
+                .iter()
+                .map(|&p| rotate_view_point(p, rotation))
+                .collect();
+            assert_eq!(view.players[0].active, expected);
+        }
+        let p = Point { x: 3, y: 7 };
+        assert_eq!(rotate_view_point(rotate_view_point(p, 180), 180), p);
+        assert_eq!(vs.players[0].board.cells[0][1], 8, "simulation untouched");
+    }
+
+    #[test]
+    fn set_field_reports_typed_errors() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        assert_eq!(
+            vs.set_field(0, &[0; 15], 10, 2, false),
+            Err(StateError::WrongLength { expected: 20, got: 15 })
+        );
+        assert_eq!(vs.set_field(0, &[9; 10], 10, 1, false), Err(StateError::InvalidColor(9)));
+        assert_eq!(vs.set_field(5, &[], 10, 0, false), Err(StateError::InvalidPlayer));
+        let full = [8; WIDTH * VISIBLE_HEIGHT];
+        assert_eq!(vs.set_field(0, &full, WIDTH, VISIBLE_HEIGHT, false), Err(StateError::OverlapsActive));
+        assert_eq!(vs.set_field(0, &full, WIDTH, VISIBLE_HEIGHT, true), Err(StateError::WouldTopOut));
+        assert!(vs.set_field(0, &[8; WIDTH], WIDTH, 1, false).is_ok());
+        assert_eq!(vs.players[0].board.cells[0], [8; WIDTH]);
+    }
+
+    #[test]
+    fn strict_settings_name_the_bad_field() {
+        let ok = parse_settings_strict(serde_json::json!({ "das": 100, "garbage": { "fixed_hole": 3 } }));
+        assert_eq!(ok.unwrap().garbage.fixed_hole, Some(3));
+
+        let typo = parse_settings_strict(serde_json::json!({ "garbage": { "fixed_hoel": 3 } }));
+        assert_eq!(typo.unwrap_err(), "unknown field settings.garbage.fixed_hoel");
+
+        let bad = parse_settings_strict(serde_json::json!({ "arr": 10, "das": "fast" })).unwrap_err();
+        assert!(bad.starts_with("settings.das: "), "{}", bad);
+    }
+
+    fn t_spin_eligible(vs: &Versus) -> bool {
+        let p = &vs.players[0];
+        detect_t_spin(&p.board, &p.active, p.last_action_was_rotation, p.last_kick)
+    }
+
+    #[test]
+    fn shifting_after_rotation_voids_t_spin() {
+        let mut vs = single_piece_versus(Tetromino::T);
+        let board = &mut vs.players[0].board;
+        // Slot under two overhangs at x=3 and x=5; the T slides in from the left.
+        board.cells[0] = [8; WIDTH];
+        board.cells[0][3] = 0;
+        board.cells[0][4] = 0;
+        board.cells[1] = [8; WIDTH];
+        for x in 2..=5 {
+            board.cells[1][x] = 0;
+        }
+        board.cells[2][3] = 8;
+        board.cells[2][5] = 8;
+
+        vs.players[0].active.rotation = Rotation::Reverse;
+        vs.players[0].active.x = 4;
+        vs.players[0].active.y = 1;
+        // As if a long kick had rotated it in, which upgrades this mini to a full spin.
+        vs.players[0].last_action_was_rotation = true;
+        vs.players[0].last_kick = (1, -2);
+        assert!(t_spin_eligible(&vs), "rotation straight into the slot is a spin");
+
+        vs.players[0].active.x = 3;
+        assert!(vs.try_shift(0, 1));
+        assert!(!t_spin_eligible(&vs), "rotate -> shift -> lock is not a spin");
+    }
+
+    #[test]
+    fn drained_events_cover_every_tick_exactly_once() {
+        let settings = GameSettings { spin_debug: true, ..GameSettings::default() };
+        let kind = RandomizerKind::SevenBag;
+        let mut client = GameClient {
+            versus: Versus::new(settings, BotConfig::default(), [kind.clone(), kind]),
+            input_states: [InputState::default(), InputState::default()],
+            prev_fields: Vec::new(),
+            event_backlog: VecDeque::new(),
+        };
+        let mut seen = Vec::new();
+        for i in 0..12 {
+            client.input_states[0].hard_drop = i % 2 == 0;
+            client.tick_versus(16.0);
+            // Mix frame-building ticks, `takeEvents` ticks and ticks nobody reads yet.
+            match i % 3 {
+                0 => {
+                    seen.extend(client.versus.events.iter().cloned());
+                    client.keep_events();
+                }
+                1 => seen.extend(client.take_frame_events()),
+                _ => {}
+            }
+        }
+        seen.extend(client.versus.events.iter().cloned());
+        let locks = seen.iter().filter(|e| matches!(e, GameEvent::SpinDebug { player: 0, .. })).count();
+        assert_eq!(locks, 6);
+        assert_eq!(format!("{:?}", client.drain_backlog()), format!("{:?}", seen));
+        assert!(client.drain_backlog().is_empty());
+    }
+
+    #[test]
+    fn spin_debug_reports_corners_and_kick_on_lock() {
+        let mut vs = single_piece_versus(Tetromino::T);
+        vs.settings.spin_debug = true;
+        let board = &mut vs.players[0].board;
+        board.cells[0] = [8; WIDTH];
+        board.cells[0][3] = 0;
+        board.cells[0][4] = 0;
+        board.cells[1] = [8; WIDTH];
+        for x in 2..=5 {
+            board.cells[1][x] = 0;
+        }
+        board.cells[2][3] = 8;
+        board.cells[2][5] = 8;
+        let player = &mut vs.players[0];
+        player.active.rotation = Rotation::Reverse;
+        player.active.x = 4;
+        player.active.y = 1;
+        player.last_action_was_rotation = true;
+        player.last_kick = (1, -2);
+
+        let settings = vs.settings.clone();
+        let (cleared, t_spin, overflow) = vs.players[0].lock_piece(&settings);
+        vs.on_piece_locked(0, cleared, t_spin, overflow);
+        let [GameEvent::SpinDebug { player: 0, debug }] = &vs.events[..] else {
+            panic!("expected one SpinDebug event, got {:?}", vs.events);
+        };
+        assert_eq!(debug.corners, [true, true, false, true]);
+        assert_eq!(debug.last_kick, (1, -2));
+        assert!(debug.spin);
+    }
+
+    #[test]
+    fn soft_drop_after_rotation_voids_t_spin() {
+        let mut vs = single_piece_versus(Tetromino::T);
+        let board = &mut vs.players[0].board;
+        for y in 0..3 {
+            board.cells[y][3] = 8;
+        }
+        board.cells[0][5] = 8;
+
+        vs.players[0].active.rotation = Rotation::Right;
+        vs.players[0].active.x = 4;
+        vs.players[0].active.y = 1;
+        vs.players[0].last_action_was_rotation = true;
+        assert!(t_spin_eligible(&vs));
+
+        // Same resting spot, but reached by one gravity step after the rotation. The engine
+        // treats that as a drop, not a spin.
+        vs.players[0].active.y = 2;
+        assert!(vs.try_fall(0));
+        assert_eq!(vs.players[0].active.y, 1);
+        assert!(!t_spin_eligible(&vs));
+    }
+
+    #[test]
+    fn bot_pps_ramps_then_holds() {
+        let ramp = BotConfig {
+            pps_start: 1.0,
+            pps_end: 3.0,
+            ramp_seconds: 60.0,
+            ..BotConfig::default()
+        };
+        assert_eq!(ramp.pps_at(0.0), 1.0);
+        assert_eq!(ramp.pps_at(30_000.0), 2.0);
+        assert_eq!(ramp.pps_at(120_000.0), 3.0);
+        assert_eq!(BotConfig::constant(2.5).pps_at(10_000.0), 2.5);
+    }
+
+    #[test]
+    fn internal_bot_walks_piece_to_target_column() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.use_internal_bot = true;
+        vs.bot_driver.config = BotConfig::constant(5.0);
+        // Columns 8-9 are the only low spot, so the bot has to slide the O across.
+        for y in 0..3 {
+            for x in 0..8 {
+                vs.players[1].board.cells[y][x] = 8;
+            }
+        }
+        for _ in 0..200 {
+            vs.tick(16.0, InputFrame::default());
+            if vs.stats[1].pieces > 0 {
+                break;
+            }
+        }
+        assert_eq!(vs.stats[1].pieces, 1);
+        assert!(!vs.players[1].topped_out);
+        // The O filled rows 0-1, clearing them and leaving the old third row at the bottom.
+        assert_eq!(vs.players[1].board.cells[0][..8], [8; 8]);
+        assert_eq!(vs.players[1].board.cells[0][8..], [0, 0]);
+        assert_eq!(vs.players[1].board.max_height(), 1);
+    }
+
+    #[test]
+    fn smooth_bot_taps_each_step_at_its_pace() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.use_internal_bot = true;
+        vs.bot_driver.config = BotConfig { smooth: true, ..BotConfig::constant(2.0) };
+        for y in 0..3 {
+            for x in 0..8 {
+                vs.players[1].board.cells[y][x] = 8;
+            }
+        }
+        let mut ticks = 0;
+        while vs.stats[1].pieces == 0 && ticks < 200 {
+            vs.tick(10.0, InputFrame::default());
+            ticks += 1;
+        }
+        // Four single taps right and a drop, spread over the 500ms a piece gets at 2 PPS.
+        assert_eq!(vs.stats[1].keys, 5);
+        assert!((48..=52).contains(&ticks), "placed after {} ticks", ticks);
+        assert_eq!(vs.players[1].board.cells[0][8..], [0, 0]);
+        assert_eq!(vs.players[1].board.max_height(), 1);
+    }
+
+    #[test]
+    fn cascade_drops_floating_cells_into_chain_clear() {
+        let mut board = Board::new();
+        for x in 0..WIDTH - 1 {
+            board.cells[0][x] = 8;
+        }
+        board.cells[1] = [8; WIDTH];
+        board.cells[2][WIDTH - 1] = 7;
+
+        let mut naive = board.clone();
+        assert_eq!(naive.clear_lines(), 1);
+        assert_eq!(naive.cells[1][WIDTH - 1], 7, "naive gravity leaves the cell floating");
+
+        assert_eq!(board.clear_lines(), 1);
+        assert_eq!(board.cascade(), vec![1]);
+        assert!(board.visible_empty());
+    }
+
+    #[test]
+    fn visible_drop_height_ignores_buffer_landings() {
+        let shape = shape_blocks(Tetromino::O, Rotation::Spawn);
+        let mut board = Board::new();
+        let floor = board.lowest_visible_drop_height(4, &shape).unwrap();
+        assert!(shape.iter().any(|b| floor + b.y as i32 == 0));
+
+        for y in 0..VISIBLE_HEIGHT - 1 {
+            board.cells[y][4] = 8;
+            board.cells[y][5] = 8;
+        }
+        assert!(board.lowest_drop_height(4, &shape).is_some());
+        assert_eq!(board.lowest_visible_drop_height(4, &shape), None);
+    }
+
+    #[test]
+    fn hole_count_ignores_buffer_cells() {
+        let mut board = Board::new();
+        // Column 0: a stack up into the buffer with one real hole and one gap in the buffer.
+        for y in 0..VISIBLE_HEIGHT + 3 {
+            board.cells[y][0] = 8;
+        }
+        board.cells[10][0] = 0;
+        board.cells[VISIBLE_HEIGHT + 1][0] = 0;
+        // Column 1: a lone buffer cell floating over an empty visible column.
+        board.cells[VISIBLE_HEIGHT][1] = 8;
+        assert_eq!(board.hole_count(), 1);
+    }
+
+    #[test]
+    fn preview_move_matches_apply_without_mutating() {
+        let mut vs = single_piece_versus(Tetromino::I);
+        for x in 0..6 {
+            vs.players[0].board.cells[0][x] = 8;
+        }
+        let before = vs.players[0].board.hash_hex();
+        let mv = tbp_move(Tetromino::I, tbp_data::Orientation::North, 7, 0);
+
+        let preview = vs.preview_tbp_move(0, &mv).unwrap();
+        assert!(preview.legal);
+        assert_eq!(preview.lines, 1);
+        assert_eq!(vs.players[0].board.hash_hex(), before);
+        assert_eq!(vs.stats[0].pieces, 0);
+
+        let applied = vs.apply_tbp_move(0, mv).unwrap();
+        assert_eq!(applied.lines_cleared, preview.lines);
+        assert_eq!(vs.players[0].board.hash_hex(), preview.resulting_hash);
+
+        let illegal = tbp_move(Tetromino::T, tbp_data::Orientation::North, 4, 0);
+        let preview = vs.preview_tbp_move(0, &illegal).unwrap();
+        assert!(!preview.legal);
+        assert!(preview.reason.is_some());
+    }
+
+    #[test]
+    fn preview_move_reports_a_strict_lock_out() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        for row in 0..VISIBLE_HEIGHT {
+            vs.players[0].board.cells[row][0] = 8;
+            vs.players[0].board.cells[row][1] = 8;
+        }
+        let mv = tbp_move(Tetromino::O, tbp_data::Orientation::North, 0, VISIBLE_HEIGHT as i32);
+        assert!(vs.preview_tbp_move(0, &mv).unwrap().legal);
+        vs.settings.lock_out_rule = LockOutRule::Strict;
+        let preview = vs.preview_tbp_move(0, &mv).unwrap();
+        assert!(!preview.legal);
+        assert!(!vs.players[0].topped_out);
+        vs.apply_tbp_move(0, mv).unwrap();
+        assert_eq!(vs.players[0].top_out_reason, Some(TopOutReason::LockOut));
+    }
+
+    #[test]
+    fn tbp_move_for_the_next_piece_reports_an_inferred_hold() {
+        let mut vs = single_piece_versus(Tetromino::I);
+        vs.set_queue(0, &[Tetromino::T.color_id()], false).unwrap();
+        let mv = tbp_move(Tetromino::T, tbp_data::Orientation::North, 4, 0);
+        let applied = vs.apply_tbp_move(0, mv).unwrap();
+        assert!(applied.inferred_hold);
+        assert_eq!(vs.players[0].hold, Some(Tetromino::I));
+
+        let mv = tbp_move(Tetromino::I, tbp_data::Orientation::North, 4, 1);
+        assert!(!vs.apply_tbp_move(0, mv).unwrap().inferred_hold);
+    }
+
+    #[test]
+    fn strict_tbp_rejects_a_colliding_y_instead_of_dropping() {
+        let mut vs = single_piece_versus(Tetromino::I);
+        for x in 0..WIDTH - 1 {
+            vs.players[0].board.cells[0][x] = 8;
+        }
+        let mv = tbp_move(Tetromino::I, tbp_data::Orientation::North, 4, 0);
+        vs.strict_tbp = true;
+        let err = vs.apply_tbp_move(0, mv.clone()).err().unwrap();
+        assert!(err.starts_with("Collision"));
+        assert!(!vs.preview_tbp_move(0, &mv).unwrap().legal);
+        assert_eq!(vs.stats[0].pieces, 0);
+
+        vs.strict_tbp = false;
+        assert!(vs.apply_tbp_move(0, mv).is_ok());
+        assert_eq!(vs.stats[0].pieces, 1);
+    }
+
+    #[test]
+    fn lookahead_depth_does_not_change_the_dealt_sequence() {
+        let dealt = |kind: RandomizerKind, depth: usize| {
+            let mut player = Player::new(kind, false, depth, &[]);
+            let mut pieces = vec![player.active.piece];
+            for i in 0..20 {
+                // Change the board between draws so board-reading kinds would notice.
+                player.board.cells[i % 4][i % WIDTH] = 8;
+                player.spawn_next();
+                pieces.push(player.active.piece);
+            }
+            pieces
+        };
+        assert_eq!(dealt(RandomizerKind::LoveTrisNoBag, 2), dealt(RandomizerKind::LoveTrisNoBag, 12));
+        assert_ne!(
+            dealt(RandomizerKind::LoveTrisOriginal, DEFAULT_LOOKAHEAD_DEPTH),
+            dealt(RandomizerKind::LoveTrisOriginal, 12),
+            "why board-reading kinds are held to the default depth"
+        );
+        assert!(RandomizerKind::LoveTrisOriginal.check_lookahead(12).is_err());
+        assert!(RandomizerKind::LoveTris.check_lookahead(DEFAULT_LOOKAHEAD_DEPTH).is_ok());
+        assert!(RandomizerKind::SevenBag.check_lookahead(12).is_ok());
+
+        let shallow = GameSettings { lookahead_depth: 3, ..GameSettings::default() };
+        assert!(shallow.validate().is_err(), "preview_count 6 > lookahead_depth 3");
+    }
+
+    #[test]
+    fn initial_queue_is_dealt_before_the_randomizer() {
+        let settings = GameSettings {
+            initial_queue: Some(vec![Tetromino::I, Tetromino::T, Tetromino::L]),
+            lookahead_depth: 2,
+            ..GameSettings::default()
+        };
+        let kind = RandomizerKind::SinglePiece { piece: Tetromino::O };
+        let vs = Versus::new(settings, BotConfig::default(), [kind.clone(), kind]);
+        for player in &vs.players {
+            assert_eq!(player.active.piece, Tetromino::I);
+            assert_eq!(player.queue, [Tetromino::T]);
+            assert_eq!(player.forced_queue, [Tetromino::L]);
+        }
+        let empty = GameSettings { initial_queue: Some(Vec::new()), ..GameSettings::default() };
+        assert!(empty.validate().is_err());
+    }
+
+    #[test]
+    fn preview_blocks_match_the_rotated_shape() {
+        let vs = single_piece_versus(Tetromino::O);
+        let i = Tetromino::I.color_id();
+        assert_eq!(vs.preview_blocks(0, i, Rotation::Right).unwrap(), shape_blocks(Tetromino::I, Rotation::Right));
+        assert!(matches!(vs.preview_blocks(0, 0, Rotation::Spawn), Err(StateError::InvalidPiece(0))));
+        assert!(matches!(vs.preview_blocks(2, i, Rotation::Spawn), Err(StateError::InvalidPlayer)));
+    }
+
+    #[test]
+    fn force_lock_locks_in_place_without_dropping() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.players[0].active.y = 5;
+        let result = vs.force_lock(0).unwrap();
+        assert_eq!(result.lines_cleared, 0);
+        assert!(!result.topped_out);
+        assert_eq!(vs.stats[0].pieces, 1);
+        assert_eq!(vs.players[0].board.cells[5][4], Tetromino::O.color_id());
+        assert_eq!(vs.players[0].board.cells[0][4], 0);
+        assert!(vs.force_lock(2).is_err());
+    }
+
+    #[test]
+    fn drought_warning_fires_once_when_a_piece_is_out_of_reach() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.set_randomizer(0, RandomizerKind::SevenBag);
+        vs.settings.drought_warning_pieces = Some(1);
+        // Deal until some piece is in neither the queue nor the bag.
+        while vs.players[0].guaranteed_droughts().unwrap().is_empty() {
+            vs.players[0].spawn_next();
+        }
+        let (piece, until) = vs.players[0].guaranteed_droughts().unwrap()[0];
+        let ahead = vs.players[0].queue.len() + vs.players[0].randomizer.bag_state().unwrap().len();
+        assert_eq!(until as usize, ahead);
+        vs.check_droughts();
+        vs.check_droughts();
+        let warnings: Vec<_> = vs
+            .events
+            .iter()
+            .filter(|e| matches!(e, GameEvent::DroughtWarning { player: 0, piece: p, .. } if *p == piece))
+            .collect();
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn replay_stops_at_the_first_illegal_move() {
+        let mut vs = single_piece_versus(Tetromino::I);
+        let moves = vec![
+            tbp_move(Tetromino::I, tbp_data::Orientation::North, 1, 0),
+            tbp_move(Tetromino::I, tbp_data::Orientation::North, 5, 0),
+            tbp_move(Tetromino::T, tbp_data::Orientation::North, 4, 1),
+            tbp_move(Tetromino::I, tbp_data::Orientation::North, 1, 1),
+        ];
+        let report = vs.replay_tbp_moves(0, moves).unwrap();
+        assert_eq!(report.results.len(), 2);
+        assert_eq!(report.failed_at, Some(2));
+        assert!(report.reason.is_some());
+        assert_eq!(report.board_hash, vs.players[0].board.hash_hex());
+        assert_eq!(vs.stats[0].pieces, 2);
+    }
+
+    #[test]
+    fn hold_into_a_blocked_spawn_is_refused_or_tops_out() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        // Only the O's two spawn columns are open at the top; a held I can't come in.
+        vs.players[0].board.cells[VISIBLE_HEIGHT - 1] = [8; WIDTH];
+        vs.players[0].board.cells[VISIBLE_HEIGHT - 1][4] = 0;
+        vs.players[0].board.cells[VISIBLE_HEIGHT - 1][5] = 0;
+        vs.players[0].hold = Some(Tetromino::I);
+
+        vs.try_hold(0);
+        assert_eq!(vs.players[0].active.piece, Tetromino::O);
+        assert_eq!(vs.players[0].hold, Some(Tetromino::I));
+        assert!(!vs.players[0].held_on_turn && !vs.players[0].topped_out);
+
+        vs.players[0].hold = None;
+        vs.set_queue(0, &[Tetromino::I.color_id()], false).unwrap();
+        vs.try_hold(0);
+        assert_eq!((vs.players[0].active.piece, vs.players[0].hold), (Tetromino::O, None));
+
+        vs.players[0].hold = Some(Tetromino::I);
+        vs.settings.hold_top_out = HoldTopOutRule::TopOut;
+        vs.try_hold(0);
+        assert_eq!(vs.players[0].active.piece, Tetromino::I);
+        assert_eq!(vs.players[0].top_out_reason, Some(TopOutReason::BlockOut));
+    }
+
+    #[test]
+    fn reselecting_the_same_randomizer_keeps_the_queue() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.set_randomizer(0, RandomizerKind::SevenBag);
+        vs.players[0].spawn_next();
+        let queue = vs.players[0].queue.clone();
+        let bag = vs.players[0].randomizer.bag_state();
+        let active = vs.players[0].active.piece;
+        vs.set_randomizer(0, RandomizerKind::SevenBag);
+        assert_eq!(vs.players[0].queue, queue);
+        assert_eq!(vs.players[0].randomizer.bag_state(), bag);
+        assert_eq!(vs.players[0].active.piece, active);
+
+        vs.set_randomizer(0, RandomizerKind::SinglePiece { piece: Tetromino::T });
+        assert!(vs.players[0].queue.iter().all(|p| *p == Tetromino::T));
+    }
+
+    #[test]
+    fn undo_to_last_clear_restores_board_and_bag() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        assert!(vs.undo_to_last_clear(0).is_err());
+        vs.set_randomizer(0, RandomizerKind::SevenBag);
+        let drop = InputFrame { hard_drop: true, ..InputFrame::default() };
+        vs.set_queue(0, &[Tetromino::I.color_id()], true).unwrap();
+        for x in 0..WIDTH - 1 {
+            vs.players[0].board.cells[0][x] = 8;
+            vs.players[0].board.cells[1][x] = 8;
+        }
+        vs.players[0].board.cells[2][0] = 8;
+        vs.try_rotate(0, true, false);
+        while vs.try_shift(0, 1) {}
+        vs.tick(16.0, drop);
+        assert_eq!(vs.stats[0].lines_cleared_total, 2);
+        let board = vs.players[0].board.hash_hex();
+        let queue = vs.players[0].queue.clone();
+        let bag = vs.players[0].randomizer.bag_state();
+        let active = vs.players[0].active.piece;
+
+        for _ in 0..3 {
+            vs.tick(16.0, InputFrame::default());
+            vs.tick(16.0, drop);
+        }
+        assert_ne!(vs.players[0].board.hash_hex(), board);
+        vs.undo_to_last_clear(0).unwrap();
+        assert_eq!(vs.players[0].board.hash_hex(), board);
+        assert_eq!(vs.players[0].queue, queue);
+        assert_eq!(vs.players[0].randomizer.bag_state(), bag);
+        assert_eq!(vs.players[0].active.piece, active);
+    }
+
+    #[test]
+    fn undo_to_last_clear_rearms_warnings_and_skips_bots() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        let set_height = |vs: &mut Versus, h: usize| {
+            vs.players[0].board.cells = [[0; WIDTH]; TOTAL_HEIGHT];
+            for y in 0..h {
+                vs.players[0].board.cells[y][0] = 8;
+            }
+        };
+        // Between the danger threshold (15) and its re-arm height (13).
+        set_height(&mut vs, 14);
+        vs.on_piece_locked(0, 1, false, false);
+        vs.on_piece_locked(1, 1, false, false);
+        assert!(vs.players[1].last_clear.is_none(), "the bot keeps no checkpoint");
+        let history = vs.players[0].dealt_history.clone();
+
+        set_height(&mut vs, 15);
+        vs.tick(1.0, InputFrame::default());
+        for _ in 0..3 {
+            vs.players[0].spawn_next();
+        }
+        vs.undo_to_last_clear(0).unwrap();
+        assert_eq!(vs.players[0].dealt_history, history);
+        vs.tick(1.0, InputFrame::default());
+        vs.events.clear();
+        set_height(&mut vs, 15);
+        vs.tick(1.0, InputFrame::default());
+        assert!(matches!(vs.events[..], [GameEvent::DangerZone { player: 0, height: 15 }]));
+    }
+
+    #[test]
+    fn guaranteed_next_includes_the_last_piece_of_a_bag() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.set_randomizer(0, RandomizerKind::SevenBag);
+        let queued: Vec<u8> = vs.players[0].queue.iter().map(|p| p.color_id()).collect();
+        assert_eq!(vs.guaranteed_next(0).unwrap(), queued);
+
+        let player = &mut vs.players[0];
+        while player.randomizer.bag_state().unwrap().len() != 1 {
+            player.spawn_next();
+        }
+        let last = player.randomizer.bag_state().unwrap()[0];
+        let known = vs.guaranteed_next(0).unwrap();
+        assert_eq!(known.len(), vs.players[0].queue.len() + 1);
+        assert_eq!(known.last(), Some(&last.color_id()));
+    }
+
+    #[test]
+    fn custom_pentomino_spawns_rotates_and_locks() {
+        let line = PieceShape {
+            blocks: (-2..=2).map(|x| Point { x, y: 0 }).collect(),
+            color: 3,
+            kicks: Vec::new(),
+        };
+        let kind = RandomizerKind::CustomPieces { shapes: vec![line] };
+        assert!(kind.validate().is_ok());
+        let mut vs = Versus::new(GameSettings::default(), BotConfig::default(), [kind.clone(), kind]);
+        assert_eq!(vs.players[0].active.blocks().len(), 5);
+        assert!(vs.snapshot().players[0].next_blocks.iter().all(|b| b.len() == 5));
+
+        // Stood up against the left wall with no kicks, it cannot rotate back flat.
+        assert!(vs.try_rotate(0, true, false));
+        vs.players[0].active.x = 0;
+        vs.players[0].active.y = 2;
+        assert!(!vs.try_rotate(0, false, false));
+
+        vs.players[0].active.x = 4;
+        vs.players[0].hard_drop(&vs.settings.clone());
+        let row: Vec<u8> = vs.players[0].board.cells[0].to_vec();
+        assert_eq!(row, [0, 0, 0, 0, 3, 0, 0, 0, 0, 0]);
+        assert_eq!(vs.players[0].board.cells[4][4], 3);
+
+        let too_big = RandomizerKind::CustomPieces {
+            shapes: vec![PieceShape {
+                blocks: vec![Point { x: 0, y: 0 }; 6],
+                color: 1,
+                kicks: Vec::new(),
+            }],
+        };
+        assert!(too_big.validate().is_err());
+    }
+
+    #[test]
+    fn disabled_kicks_stop_wall_kicks() {
+        for kicks_enabled in [true, false] {
+            let mut vs = single_piece_versus(Tetromino::I);
+            vs.settings.kicks_enabled = kicks_enabled;
+            assert!(vs.try_rotate(0, true, false));
+            // Vertical I flush against the left wall; turning flat needs a kick off it.
+            while vs.try_shift(0, -1) {}
+            assert_eq!(vs.try_rotate(0, false, false), kicks_enabled);
+        }
+    }
+
+    #[test]
+    fn dry_streaks_and_lines_per_piece() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        for cleared in [0, 0, 0, 2, 0] {
+            vs.on_piece_locked(0, cleared, false, false);
+        }
+        let stats = vs.detailed_stats(0).unwrap();
+        assert_eq!(stats.longest_dry_streak, 3);
+        assert_eq!(vs.stats[0].dry_streak, 1);
+        assert!((stats.lines_per_piece - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn paused_player_freezes_while_the_other_plays() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.use_internal_bot = true;
+        vs.set_player_paused(1, true).unwrap();
+        vs.players[1].active.y = 0;
+        vs.players[1].active.lock_timer = 1.0;
+        vs.inject_garbage(0, 2).unwrap();
+        let bot_piece = (vs.players[1].active.x, vs.players[1].active.y);
+
+        for _ in 0..5 {
+            vs.tick(400.0, InputFrame::default());
+        }
+        assert!(vs.players[0].active.y < VISIBLE_HEIGHT as i32 - 1);
+        assert_eq!((vs.players[1].active.x, vs.players[1].active.y), bot_piece);
+        assert_eq!((vs.stats[1].time_ms, vs.stats[1].active_play_ms), (2000.0, 0.0));
+        let pending: u32 = vs.players[0].pending_garbage.iter().map(|b| b.lines).sum();
+        assert_eq!(pending, 2);
+
+        vs.set_player_paused(1, false).unwrap();
+        assert_eq!(vs.players[1].active.lock_timer, LOCK_DELAY_MS);
+        assert!(vs.set_player_paused(2, true).is_err());
+    }
+
+    #[test]
+    fn paused_time_does_not_count_toward_rates() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.tick(500.0, InputFrame::default());
+        vs.set_player_paused(0, true).unwrap();
+        vs.tick(1500.0, InputFrame::default());
+        vs.set_player_paused(0, false).unwrap();
+        vs.tick(500.0, InputFrame { hard_drop: true, ..InputFrame::default() });
+        let stats = &vs.stats[0];
+        assert_eq!((stats.time_ms, stats.active_play_ms), (2500.0, 1000.0));
+        assert_eq!(stats.pps(), 1.0);
+    }
+
+    #[test]
+    fn zero_arr_shifts_to_the_wall_once_das_charges() {
+        let x_after_das = |mode: ArrMode| {
+            let mut vs = single_piece_versus(Tetromino::O);
+            vs.settings.arr = 0;
+            vs.settings.arr_mode = mode;
+            vs.settings.das = 2;
+            let left = InputFrame { left: true, ..InputFrame::default() };
+            vs.tick(1.0, left);
+            vs.tick(1.0, left);
+            vs.players[0].active.x
+        };
+        assert_eq!(x_after_das(ArrMode::Auto), 0);
+        assert_eq!(x_after_das(ArrMode::Instant), 0);
+        assert_eq!(x_after_das(ArrMode::Stepped), 2, "initial shift, then one 1 ms step");
+    }
+
+    #[test]
+    fn countdown_charges_das_without_moving_pieces() {
+        let settings = GameSettings { countdown_ms: 1000.0, arr: 0, ..GameSettings::default() };
+        let kind = RandomizerKind::SinglePiece { piece: Tetromino::T };
+        let mut vs = Versus::new(settings, BotConfig::default(), [kind.clone(), kind]);
+        let start = (vs.players[0].active.x, vs.players[0].active.y);
+        let held = InputFrame { left: true, rotate_cw: true, hard_drop: true, ..InputFrame::default() };
+        for _ in 0..5 {
+            vs.tick(200.0, held);
+        }
+        assert_eq!((vs.players[0].active.x, vs.players[0].active.y), start);
+        assert_eq!((vs.countdown_ms, vs.stats[0].time_ms), (0.0, 0.0));
+        assert_eq!(vs.controllers[0].das_timer, vs.settings.das as f32);
+
+        vs.tick(1.0, held);
+        let active = &vs.players[0].active;
+        assert_eq!(vs.stats[0].pieces, 0, "the held hard drop doesn't fire at go");
+        assert_ne!(active.rotation, Rotation::Spawn, "held rotation applies on the first frame");
+        assert!(vs.players[0].board.collision(&ActivePiece { x: active.x - 1, ..active.clone() }));
+    }
+
+    #[test]
+    fn net_garbage_counts_rows_added_to_the_field() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.players[0].board.cells[0][0] = 8;
+        vs.on_piece_locked(0, 4, false, false);
+        vs.inject_garbage(0, 3).unwrap();
+        vs.on_piece_locked(0, 0, false, false);
+        let view = vs.stats_view(0);
+        assert_eq!((view.garbage_sent, view.garbage_received), (4, 3));
+        assert_eq!(view.net_garbage, 1);
+    }
+
+    #[test]
+    fn bot_tie_break_picks_a_fixed_column() {
+        let board = Board::new();
+        let col = |tie_break| find_safe_column(&board, Tetromino::O, tie_break).unwrap().x;
+        assert_eq!(col(BotTieBreak::LeftToRight), 0);
+        assert_eq!(col(BotTieBreak::CenterOut), 4);
+    }
+
+    #[test]
+    fn simulate_bot_stops_at_top_out_or_piece_cap() {
+        let bot = BotConfig::constant(20.0);
+        let capped = simulate_bot(GameSettings::default(), RandomizerKind::SevenBag, bot, 1, 5);
+        assert_eq!((capped.pieces, capped.topped_out), (5, false));
+
+        let s_only = RandomizerKind::SinglePiece { piece: Tetromino::S };
+        let run = simulate_bot(GameSettings::default(), s_only, bot, 1, 1000);
+        assert!(run.topped_out && run.pieces < 1000);
+        assert!(run.max_height >= VISIBLE_HEIGHT - 2, "{:?}", run);
+    }
+
+    #[test]
+    fn simulate_bot_replays_from_its_seed() {
+        let bot = BotConfig { tie_break: BotTieBreak::CenterOut, ..BotConfig::constant(20.0) };
+        let run = |seed| simulate_bot(GameSettings::default(), RandomizerKind::TrueRandom, bot, seed, 1000);
+        let first = run(7);
+        assert_eq!(run(7), first);
+        assert!((1..=20).any(|seed| run(seed) != first), "other seeds deal other games");
+    }
+
+    #[test]
+    fn timed_soft_drop_lock_fires_after_holding_on_the_floor() {
+        let soft = InputFrame { soft_drop: true, ..InputFrame::default() };
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.settings.soft_drop_lock_ms = Some(150.0);
+        vs.players[0].active = vs.ghost_piece(0);
+        vs.tick(100.0, soft);
+        assert!(matches!(
+            vs.events[..],
+            [GameEvent::SoftLockPending { player: 0, remaining_ms }] if remaining_ms == 150.0
+        ));
+        vs.tick(100.0, soft);
+        assert_eq!(vs.stats[0].pieces, 0);
+        vs.tick(100.0, soft);
+        assert_eq!(vs.stats[0].pieces, 1, "locks well inside the normal delay");
+    }
+
+    #[test]
+    fn send_then_cancel_sends_attack_over_incoming_garbage() {
+        for send_then_cancel in [false, true] {
+            let mut vs = single_piece_versus(Tetromino::O);
+            vs.settings.garbage.send_then_cancel = send_then_cancel;
+            vs.players[0].board.cells[0][0] = 8;
+            vs.inject_garbage(0, 3).unwrap();
+            // A tetris is worth 4; cancel-first spends 3 of it on the incoming garbage.
+            vs.on_piece_locked(0, 4, false, false);
+            let pending: u32 = vs.players[0].pending_garbage.iter().map(|b| b.lines).sum();
+            let expected = if send_then_cancel { (4, 0, 3) } else { (1, 3, 0) };
+            assert_eq!((vs.stats[0].garbage_sent, vs.stats[0].garbage_cancelled, pending), expected);
+        }
+    }
+
+    #[test]
+    fn final_results_count_clears_and_top_out_reason() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.players[0].board.cells[0][0] = 8;
+        vs.on_piece_locked(0, 4, false, false);
+        vs.on_piece_locked(0, 1, false, false);
+        vs.players[1].board.cells[0][0] = 8;
+        vs.inject_garbage(1, VISIBLE_HEIGHT as u32 + 1).unwrap();
+        vs.on_piece_locked(1, 0, false, false);
+
+        let results = vs.final_results();
+        assert_eq!(results[0].tetrises, 1);
+        assert_eq!(results[0].max_combo, 2);
+        assert_eq!(results[0].top_out, None);
+        assert_eq!(results[1].top_out, Some(TopOutReason::GarbageOut));
+    }
+
+    #[test]
+    fn garbage_that_buries_the_spawn_tops_out_at_once() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        for idx in 0..2 {
+            for y in 0..VISIBLE_HEIGHT / 2 {
+                vs.players[idx].board.cells[y][4] = 3;
+            }
+        }
+        vs.inject_garbage(0, 40).unwrap();
+        vs.land_pending_garbage(0);
+        assert_eq!(vs.players[0].top_out_reason, Some(TopOutReason::GarbageOut));
+
+        // Exactly full to the spawn row: no overflow, but the next piece has nowhere to go.
+        vs.inject_garbage(1, VISIBLE_HEIGHT as u32 / 2).unwrap();
+        vs.land_pending_garbage(1);
+        assert_eq!(vs.players[1].board.max_height(), VISIBLE_HEIGHT);
+        assert_eq!(vs.players[1].top_out_reason, Some(TopOutReason::GarbageOut));
+    }
+
+    #[test]
+    fn second_player_can_take_keyboard_input() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.set_human(1, true).unwrap();
+        vs.set_human(0, false).unwrap();
+        let p0_y = vs.players[0].active.y;
+        let drop = InputFrame { hard_drop: true, ..InputFrame::default() };
+        vs.tick2(1000.0, drop, drop);
+        assert_eq!(vs.stats[1].pieces, 1);
+        assert!(matches!(vs.controller_kind(1), ControllerKind::Human));
+        // Player 0 is now waiting on TBP, so neither its keys nor gravity move it.
+        assert_eq!(vs.stats[0].pieces, 0);
+        assert_eq!(vs.players[0].active.y, p0_y);
+        assert!(matches!(vs.controller_kind(0), ControllerKind::ExternalBot));
+    }
+
+    #[test]
+    fn lock_progress_runs_from_landing_to_lock() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        assert_eq!(vs.snapshot().players[0].lock_progress, 0.0);
+        vs.players[0].active.y = 0;
+        assert_eq!(vs.snapshot().players[0].lock_progress, 0.0);
+        vs.players[0].active.lock_timer = LOCK_DELAY_MS / 4.0;
+        assert_eq!(vs.snapshot().players[0].lock_progress, 0.75);
+        vs.players[0].active.y = 5;
+        assert_eq!(vs.snapshot().players[0].lock_progress, 0.0);
+    }
+
+    #[test]
+    fn spawn_safe_frame_lands_garbage_after_one_frame() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.settings.garbage.spawn_safe_frame = true;
+        vs.settings.garbage.fixed_hole = Some(9);
+        vs.players[0].board.cells[0][0] = 8;
+        vs.inject_garbage(0, 3).unwrap();
+        let drop = InputFrame { hard_drop: true, ..InputFrame::default() };
+        vs.tick(16.0, drop);
+        assert_eq!(vs.stats[0].pieces, 1);
+        assert_eq!(vs.players[0].pending_garbage.len(), 1);
+        vs.tick(16.0, InputFrame::default());
+        assert_eq!(vs.players[0].pending_garbage.len(), 1, "the new piece gets a safe frame");
+        vs.tick(16.0, InputFrame::default());
+        assert!(vs.players[0].pending_garbage.is_empty());
+        assert_eq!(vs.players[0].board.garbage_holes(), vec![9, 9, 9]);
+        assert!(!vs.players[0].board.collision(&vs.players[0].active));
+    }
+
+    #[test]
+    fn one_line_perfect_clear_sends_the_bonus() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        let pc = vs.attack_table.perfect_clear as u32;
+        vs.on_piece_locked(0, 0, false, false);
+        assert_eq!(vs.stats[0].attack, 0, "an empty field without a clear is no perfect clear");
+
+        vs.on_piece_locked(0, 1, false, false);
+        assert_eq!(vs.stats[0].perfect_clears, 1);
+        assert_eq!(vs.stats[0].attack, pc);
+        assert_eq!((vs.stats[0].garbage_sent, vs.stats[0].pc_attack), (pc, pc));
+
+        vs.inject_garbage(0, 4).unwrap();
+        vs.on_piece_locked(0, 1, false, false);
+        // The whole attack is the bonus here, so cancelling comes out of it.
+        assert_eq!(vs.stats[0].garbage_cancelled, 4);
+        assert_eq!(vs.stats[0].garbage_sent, pc + pc - 4);
+        assert_eq!(vs.stats[0].pc_attack, vs.stats[0].garbage_sent);
+    }
+
+    #[test]
+    fn max_combo_for_attack_flattens_combo_bonus() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.settings.combo.max_combo_for_attack = Some(3);
+        vs.players[0].board.cells[0][0] = 8;
+        let mut sent = Vec::new();
+        for _ in 0..8 {
+            let before = vs.stats[0].attack;
+            vs.on_piece_locked(0, 1, false, false);
+            sent.push(vs.stats[0].attack - before);
+        }
+        // Default table: c2 = 1 from the third clear on, and c5+ (2 and up) never reached.
+        assert_eq!(sent, [0, 0, 1, 1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn randomizer_kinds_match_serde_names_and_bags() {
+        for kind in RandomizerKind::catalog() {
+            let info = kind.info();
+            let json = serde_json::to_value(&kind).unwrap();
+            let tag = match &json {
+                serde_json::Value::Object(o) => o.keys().next().cloned(),
+                other => other.as_str().map(str::to_string),
+            };
+            assert_eq!(tag.as_deref(), Some(info.name));
+            if info.name != "CustomPieces" {
+                let bag = randomizer_from_kind(kind).bag_state();
+                assert_eq!(bag.is_some(), info.bag_state, "{}", info.name);
+            }
+        }
+        assert_eq!(randomizer_kinds().len(), RandomizerKind::catalog().len());
+    }
+
+    #[test]
+    fn attack_tables_without_mini_doubles_and_triples_load_defaults() {
+        let mut old = serde_json::to_value(default_attack_table()).unwrap();
+        let map = old.as_object_mut().unwrap();
+        map.remove("t_spin_mini_double");
+        map.remove("t_spin_mini_triple");
+        let table: AttackTable = serde_json::from_value(old).unwrap();
+        assert_eq!((table.t_spin_mini_double, table.t_spin_mini_triple), (1, 2));
+    }
+
+    #[test]
+    fn t_spin_mini_single_scores_from_the_mini_row() {
+        let mut vs = single_piece_versus(Tetromino::T);
+        vs.attack_table.t_spin_mini_single = 3;
+        // Flat T on the floor: both back corners are the floor, one front corner is filled.
+        let board = &mut vs.players[0].board;
+        board.cells[0] = [8; WIDTH];
+        for x in 3..=5 {
+            board.cells[0][x] = 0;
+        }
+        board.cells[1][3] = 8;
+        let player = &mut vs.players[0];
+        player.active.x = 4;
+        player.active.y = 0;
+        player.last_action_was_rotation = true;
+        player.last_kick = (0, 0);
+
+        let settings = vs.settings.clone();
+        let (cleared, t_spin, overflow) = vs.players[0].lock_piece(&settings);
+        assert_eq!((cleared, t_spin), (1, false));
+        vs.on_piece_locked(0, cleared, t_spin, overflow);
+        assert_eq!(vs.stats[0].attack, 3);
+    }
+
+    #[test]
+    fn solo_mode_counts_attack_without_sending_it() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.settings.versus_enabled = false;
+        vs.players[0].board.cells[0][0] = 8;
+        vs.inject_garbage(0, 2).unwrap();
+        vs.on_piece_locked(0, 4, false, false);
+        vs.on_piece_locked(0, 0, false, false);
+        assert_eq!(vs.stats[0].attack, 4);
+        assert_eq!((vs.stats[0].garbage_sent, vs.stats[0].garbage_cancelled), (0, 0));
+        assert!(vs.players[1].pending_garbage.is_empty());
+        assert_eq!(vs.players[0].pending_garbage.len(), 1, "incoming garbage never lands");
+        assert_eq!(vs.players[0].board.max_height(), 1);
+    }
+
+    #[test]
+    fn mutual_top_out_in_one_tick_is_a_draw() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.settings.garbage.rise_interval_ms = 10.0;
+        for idx in 0..2 {
+            vs.players[idx].board.cells[0][0] = 8;
+            vs.inject_garbage(idx, VISIBLE_HEIGHT as u32).unwrap();
+            vs.land_pending_garbage(idx);
+        }
+        assert_eq!(vs.result(), None);
+        vs.tick(1000.0, InputFrame::default());
+        assert!(vs.players[0].topped_out && vs.players[1].topped_out);
+        assert_eq!(vs.result(), Some(GameResult::Draw));
+
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.players[1].board.cells[0][0] = 8;
+        vs.inject_garbage(1, VISIBLE_HEIGHT as u32).unwrap();
+        vs.land_pending_garbage(1);
+        vs.tick(16.0, InputFrame::default());
+        assert_eq!(vs.result(), Some(GameResult::Win { player: 0 }));
+    }
+
+    #[test]
+    fn soft_drop_cells_count_only_rows_beyond_gravity() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.settings.soft_drop = SoftDropSpeed::Medium;
+        let start = vs.players[0].active.y;
+        let held = InputFrame { soft_drop: true, ..InputFrame::default() };
+        for _ in 0..30 {
+            vs.tick(100.0, held);
+        }
+        // Three seconds at 1 row/s gravity, doubled by soft drop: half the rows are extra.
+        assert_eq!(start - vs.players[0].active.y, 6);
+        assert_eq!(vs.stats[0].soft_drop_cells, 3);
+
+        for _ in 0..20 {
+            vs.tick(100.0, InputFrame::default());
+        }
+        assert_eq!(vs.stats[0].soft_drop_cells, 3);
+    }
+
+    #[test]
+    fn fall_debug_splits_gravity_and_soft_drop_rows() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.settings.fall_debug = true;
+        vs.settings.soft_drop = SoftDropSpeed::Medium;
+        let held = InputFrame { soft_drop: true, ..InputFrame::default() };
+        vs.tick(1500.0, held);
+        let debug = vs.fall_debug[0];
+        assert_eq!((debug.gravity_rows, debug.soft_drop_rows), (1, 1));
+        assert_eq!((debug.fall_accum, debug.soft_drop_accum), (500.0, 500.0));
+        vs.tick(600.0, InputFrame::default());
+        let debug = vs.fall_debug[0];
+        assert_eq!((debug.gravity_rows, debug.soft_drop_rows), (1, 0));
+        assert_eq!(debug.soft_drop_accum, 0.0);
+    }
+
+    #[test]
+    fn spawn_lock_grace_holds_only_freshly_spawned_pieces() {
+        let ticks_to_lock = |grace: f32, start_y: i32, dt: f32| {
+            let mut vs = single_piece_versus(Tetromino::O);
+            vs.settings.soft_drop_lock = true;
+            vs.settings.spawn_lock_grace_ms = grace;
+            vs.players[0].active.y = start_y;
+            let held = InputFrame { soft_drop: true, ..InputFrame::default() };
+            (1..100).find(|_| {
+                vs.tick(dt, held);
+                vs.stats[0].pieces > 0
+            })
+        };
+        // Spawned straight onto the floor: soft drop lock waits out the grace.
+        assert_eq!(ticks_to_lock(0.0, 0, 16.0), Some(2));
+        assert_eq!(ticks_to_lock(100.0, 0, 16.0), Some(7));
+        // A piece that had to fall first is past the grace by the time it lands.
+        assert_eq!(ticks_to_lock(100.0, 3, 400.0), ticks_to_lock(0.0, 3, 400.0));
+    }
+
+    #[test]
+    fn hard_drop_grace_lands_then_locks_on_timeout_or_second_press() {
+        let drop = InputFrame { hard_drop: true, ..InputFrame::default() };
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.settings.hard_drop_grace_ms = 50.0;
+        vs.tick(16.0, drop);
+        assert_eq!((vs.stats[0].pieces, vs.players[0].active.y), (0, 0));
+        vs.tick(16.0, InputFrame::default());
+        vs.tick(16.0, drop);
+        assert_eq!(vs.stats[0].pieces, 1, "a second press locks at once");
+
+        vs.tick(16.0, InputFrame::default());
+        vs.tick(16.0, drop);
+        let ticks = (1..10).find(|_| {
+            vs.tick(16.0, InputFrame::default());
+            vs.stats[0].pieces > 1
+        });
+        assert_eq!(ticks, Some(4));
+    }
+
+    #[test]
+    fn absolute_soft_drop_ignores_base_gravity() {
+        let rows_after = |gravity_ms: f32, mode: SoftDropMode| {
+            let mut vs = single_piece_versus(Tetromino::O);
+            vs.settings.soft_drop_mode = mode;
+            vs.gravity_ms = gravity_ms;
+            let held = InputFrame { soft_drop: true, ..InputFrame::default() };
+            for _ in 0..10 {
+                vs.tick(10.0, held);
+            }
+            VISIBLE_HEIGHT as i32 - 1 - vs.players[0].active.y
+        };
+        // 100 ms at 10 ms per row, less the row still accumulating.
+        let absolute = SoftDropMode::Absolute { ms_per_row: 10.0 };
+        assert_eq!(rows_after(1000.0, absolute), 9);
+        assert_eq!(rows_after(200.0, absolute), 9);
+        assert_eq!(rows_after(1000.0, SoftDropMode::Multiplier), 0);
+        // Gravity already faster than the soft drop speed wins: straight to the floor.
+        assert_eq!(rows_after(5.0, absolute), VISIBLE_HEIGHT as i32 - 1);
+    }
+
+    #[test]
+    fn piece_locked_reports_presses_in_order() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.settings.record_inputs = true;
+        vs.tick(16.0, InputFrame { left: true, ..InputFrame::default() });
+        vs.tick(16.0, InputFrame::default());
+        vs.tick(16.0, InputFrame { left: true, rotate_cw: true, ..InputFrame::default() });
+        vs.tick(16.0, InputFrame { hard_drop: true, ..InputFrame::default() });
+        let locked: Vec<_> = vs
+            .events
+            .iter()
+            .filter_map(|e| match e {
+                GameEvent::PieceLocked { player: 0, inputs } => Some(inputs.clone()),
+                _ => None,
+            })
+            .collect();
+        use InputButton::*;
+        assert_eq!(locked, [vec![Left, Left, RotateCw, HardDrop]]);
+        assert!(vs.controllers[0].piece_inputs.is_empty());
+    }
+
+    #[test]
+    fn legal_placements_are_distinct_and_count_clears() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        assert_eq!(vs.legal_placements(0).unwrap().len(), 9, "one per column pair, rotations merged");
+
+        vs.players[0].active = ActivePiece::new(Tetromino::I);
+        for x in 0..WIDTH - 1 {
+            vs.players[0].board.cells[0][x] = 8;
+        }
+        let placements = vs.legal_placements(0).unwrap();
+        assert_eq!(placements.len(), 7 + 10, "flat I spans 7 spots, upright I stands in 10");
+        assert_eq!(placements.iter().filter(|p| p.lines == 1).count(), 1);
+        assert!(vs.legal_placements(2).is_err());
+    }
+
+    #[test]
+    fn max_lock_time_caps_stalling_with_resets() {
+        let mut vs = single_piece_versus(Tetromino::T);
+        vs.settings.max_lock_time_ms = 1000.0;
+        vs.players[0].active = vs.ghost_piece(0);
+        vs.tick(16.0, InputFrame::default());
+        let mut ticks = 0;
+        while vs.stats[0].pieces == 0 && ticks < 100 {
+            // Keep the lock delay topped up as if resets never ran out.
+            vs.players[0].active.lock_timer = LOCK_DELAY_MS;
+            vs.tick(100.0, InputFrame::default());
+            ticks += 1;
+        }
+        assert_eq!(ticks, 10);
+    }
+
+    #[test]
+    fn default_max_lock_time_leaves_room_for_every_reset() {
+        let mut vs = single_piece_versus(Tetromino::T);
+        vs.players[0].active = vs.ghost_piece(0);
+        vs.tick(16.0, InputFrame::default());
+        for _ in 0..MOVE_RESETS {
+            // Spend nearly a full lock delay, then reset it as a move would.
+            vs.tick(LOCK_DELAY_MS - 10.0, InputFrame::default());
+            let piece = &mut vs.players[0].active;
+            piece.lock_timer = LOCK_DELAY_MS;
+            piece.move_resets -= 1;
+        }
+        assert_eq!(vs.stats[0].pieces, 0);
+    }
+
+    #[test]
+    fn garbage_rows_include_rows_with_filled_holes() {
+        let mut board = Board::new();
+        board.add_garbage(&[GarbageBatch { lines: 3, hole: 2 }]);
+        board.cells[1][2] = Tetromino::L.color_id();
+        board.cells[3][0] = Tetromino::T.color_id();
+        assert_eq!(board.garbage_rows(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn garbage_lands_with_fixed_hole_under_the_stack() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.settings.garbage.fixed_hole = Some(7);
+        vs.players[0].board.cells[0][0] = 3;
+        vs.inject_garbage(0, 2).unwrap();
+        vs.settings.garbage.fixed_hole = Some(2);
+        vs.inject_garbage(0, 1).unwrap();
+        vs.land_pending_garbage(0);
+
+        let board = &vs.players[0].board;
+        // Batches land in order, so the last one ends up at the bottom.
+        assert_eq!(board.garbage_holes(), vec![2, 7, 7]);
+        assert_eq!(board.cells[3][0], 3);
+        assert!(!vs.players[0].topped_out);
+
+        let mut board = Board::new();
+        let chunks = [GarbageBatch { lines: 2, hole: 4 }, GarbageBatch { lines: 1, hole: 9 }];
+        assert!(!board.add_garbage(&chunks));
+        assert_eq!(board.garbage_holes(), vec![9, 4, 4]);
+        let rest = GarbageBatch {
+            lines: VISIBLE_HEIGHT as u32 - 3,
+            hole: 0,
+        };
+        assert!(!board.add_garbage(&[rest]));
+        assert!(
+            board.add_garbage(&[GarbageBatch { lines: 1, hole: 0 }]),
+            "one row past the visible field overflows"
+        );
+    }
+
+    #[test]
+    fn strict_lock_out_only_for_pieces_entirely_above_the_field() {
+        for (y, topped_out) in [(VISIBLE_HEIGHT as i32 - 1, false), (VISIBLE_HEIGHT as i32, true)] {
+            let mut vs = single_piece_versus(Tetromino::O);
+            vs.settings.lock_out_rule = LockOutRule::Strict;
+            // Rest the O on a column stacked up to `y`, away from the spawn columns.
+            for row in 0..y as usize {
+                vs.players[0].board.cells[row][0] = 8;
+            }
+            vs.players[0].active.x = 0;
+            vs.players[0].active.y = y;
+            let (_, _, overflow) = vs.players[0].lock_piece(&vs.settings.clone());
+            assert!(!overflow);
+            assert_eq!(vs.players[0].topped_out, topped_out);
+            assert_eq!(
+                vs.players[0].top_out_reason,
+                topped_out.then_some(TopOutReason::LockOut)
+            );
+        }
+    }
+
+    #[test]
+    fn soft_drop_lock_reset_is_opt_in() {
+        for resets in [false, true] {
+            let mut vs = single_piece_versus(Tetromino::O);
+            vs.settings.soft_drop = SoftDropSpeed::Fast;
+            vs.settings.soft_drop_resets_lock = resets;
+            vs.players[0].active.y = 1;
+            vs.players[0].active.just_spawned = false;
+            vs.players[0].active.lock_timer = 400.0;
+            // Too short for gravity alone; the soft drop share drops the O onto the floor.
+            vs.tick(300.0, InputFrame { soft_drop: true, ..InputFrame::default() });
+            let piece = &vs.players[0].active;
+            assert_eq!(piece.y, 0);
+            let expected = if resets { (14, 200.0) } else { (15, 100.0) };
+            assert_eq!((piece.move_resets, piece.lock_timer), expected);
+        }
+    }
+
+    #[test]
+    fn garbage_script_delivers_on_schedule() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.players[0].board.cells[0][0] = 8;
+        vs.load_garbage_script(vec![
+            ScriptedAttack { time_ms: 500.0, lines: 1 },
+            ScriptedAttack { time_ms: 100.0, lines: 2 },
+        ]);
+        vs.tick(200.0, InputFrame::default());
+        assert_eq!(vs.garbage_script_report().pending, 2);
+
+        // A double (1 attack) cancels one line; a dry piece then lands the other two.
+        vs.on_piece_locked(0, 2, false, false);
+        vs.tick(400.0, InputFrame::default());
+        vs.on_piece_locked(0, 0, false, false);
+        let report = vs.garbage_script_report();
+        assert_eq!((report.attacks_delivered, report.attacks_remaining), (2, 0));
+        assert_eq!((report.lines_scripted, report.cancelled, report.tanked), (3, 1, 2));
+    }
+
+    #[test]
+    fn tbp_lifecycle_messages_are_tagged() {
+        let stop = tbp_message_json(frontend_msg::Stop::new().into()).unwrap();
+        let quit = tbp_message_json(frontend_msg::Quit::new().into()).unwrap();
+        assert_eq!(stop, r#"{"type":"stop"}"#);
+        assert_eq!(quit, r#"{"type":"quit"}"#);
+    }
+
+    #[test]
+    fn per_line_attack_mode_replaces_the_table() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.settings.attack_mode = AttackMode::PerLine {
+            per_line: 1,
+            offset: -1,
+            t_spin_multiplier: 2,
+        };
+        vs.players[0].board.cells[0][0] = 8;
+        for (cleared, t_spin) in [(1, false), (3, false), (2, true)] {
+            vs.on_piece_locked(0, cleared, t_spin, false);
+            // Break the combo so only the base attack counts.
+            vs.on_piece_locked(0, 0, false, false);
+        }
+        assert_eq!(vs.stats[0].attack, 2 + 2);
+    }
+
+    #[test]
+    fn outline_ghost_keeps_the_lowest_cell_per_column() {
+        let mut vs = single_piece_versus(Tetromino::T);
+        let full = vs.ghost(0);
+        assert_eq!(full.len(), 4);
+        vs.settings.ghost_style = GhostStyle::Outline;
+        let mut outline: Vec<(i8, i8)> = vs.ghost(0).iter().map(|p| (p.x, p.y)).collect();
+        outline.sort();
+        // A flat T on the floor: the stem above the middle cell is dropped.
+        assert_eq!(outline, [(3, 0), (4, 0), (5, 0)]);
+        vs.settings.ghost_enabled = false;
+        assert!(vs.ghost(0).is_empty());
+    }
+
+    #[test]
+    fn danger_zone_fires_once_until_the_stack_drops() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        let danger = |vs: &Versus| {
+            vs.events.iter().filter(|e| matches!(e, GameEvent::DangerZone { player: 0, .. })).count()
+        };
+        let set_height = |vs: &mut Versus, h: usize| {
+            vs.players[0].board.cells = [[0; WIDTH]; TOTAL_HEIGHT];
+            for y in 0..h {
+                vs.players[0].board.cells[y][0] = 8;
+            }
+        };
+        set_height(&mut vs, 15);
+        vs.tick(1.0, InputFrame::default());
+        assert!(matches!(vs.events[..], [GameEvent::DangerZone { player: 0, height: 15 }]));
+        // Hovering around the threshold stays quiet.
+        set_height(&mut vs, 14);
+        vs.tick(1.0, InputFrame::default());
+        set_height(&mut vs, 15);
+        vs.tick(1.0, InputFrame::default());
+        assert_eq!(danger(&vs), 1);
+        set_height(&mut vs, 12);
+        vs.tick(1.0, InputFrame::default());
+        set_height(&mut vs, 16);
+        vs.tick(1.0, InputFrame::default());
+        assert_eq!(danger(&vs), 2);
+    }
+
+    #[test]
+    fn broadcast_snapshot_reports_incoming_garbage() {
+        let mut vs = single_piece_versus(Tetromino::O);
+        vs.players[1].board.cells[0][0] = 8;
+        vs.inject_garbage(0, 3).unwrap();
+
+        let view = vs.broadcast_snapshot();
+        assert_eq!(view.players[0].attacked_by, Some(1));
+        assert_eq!(view.players[0].stats.pending_garbage, 3);
+        assert_eq!(view.players[1].attacked_by, None);
+        assert_eq!(view.players[1].field[0], 8);
+        assert_eq!(view.players[1].field.len(), WIDTH * VISIBLE_HEIGHT);
+    }
+}
+
+#[wasm_bindgen]
+pub struct GameClient {
+    versus: Versus,
+    input_states: [InputState; 2],
+    // Last field sent to the frontend per player, for `tickDelta`.
+    prev_fields: Vec<Vec<u8>>,
+    // Events already handed out in frames or by `takeEvents`, kept until `drainEvents`.
+    event_backlog: VecDeque<GameEvent>,
+}
+
+impl GameClient {
+    /// Move this frame's events into the backlog once the frame has been built.
+    fn keep_events(&mut self) {
+        self.event_backlog.extend(self.versus.events.drain(..));
+    }
+
+    /// Hand out the pending events, keeping a copy for `drain_backlog`.
+    fn take_frame_events(&mut self) -> Vec<GameEvent> {
+        let events = std::mem::take(&mut self.versus.events);
+        self.event_backlog.extend(events.iter().cloned());
+        events
+    }
+
+    fn drain_backlog(&mut self) -> Vec<GameEvent> {
+        self.keep_events();
+        self.event_backlog.drain(..).collect()
+    }
+
+    fn tick_versus(&mut self, dt_ms: f32) {
+        let [input0, input1] = self.input_states.clone().map(InputFrame::from);
+        self.versus.tick2(dt_ms, input0, input1);
+    }
+}
+
+#[wasm_bindgen]
+impl GameClient {
+    #[wasm_bindgen(constructor)]
+    /// With `strict`, malformed or unknown settings/randomizers are an error naming the
+    /// offending field; otherwise they quietly fall back to defaults.
+    pub fn new(
+        settings: JsValue,
+        bot_pps: f32,
+        randomizers: JsValue,
+        strict: Option<bool>,
+    ) -> Result<GameClient, JsValue> {
+        let (settings, randomizers) = if strict.unwrap_or(false) {
+            let settings = from_value(settings)
+                .map_err(|e| format!("settings: {}", e))
+                .and_then(parse_settings_strict)
+                .map_err(|e| JsValue::from_str(&e))?;
+            let randomizers: [RandomizerKind; 2] = from_value(randomizers)
+                .map_err(|e| JsValue::from_str(&format!("randomizers: {}", e)))?;
+            (settings, randomizers)
+        } else {
+            let settings: GameSettings = from_value(settings).unwrap_or_default();
+            let randomizers: [RandomizerKind; 2] = from_value(randomizers)
+                .unwrap_or([RandomizerKind::SevenBag, RandomizerKind::SevenBag]);
+            (settings, randomizers)
+        };
+        settings.validate().map_err(|e| JsValue::from_str(&e))?;
+        for kind in &randomizers {
+            kind.validate().map_err(|e| JsValue::from_str(&e))?;
+            kind.check_lookahead(settings.lookahead_depth)
+                .map_err(|e| JsValue::from_str(&e))?;
+        }
+        let versus = Versus::new(settings, BotConfig::constant(bot_pps), randomizers);
+        Ok(Self {
+            versus,
+            input_states: [InputState::default(), InputState::default()],
+            prev_fields: Vec::new(),
+            event_backlog: VecDeque::new(),
+        })
+    }
+
+    /// Metadata for every randomizer kind: name, parameter fields, bag-state support and a
+    /// short description.
+    #[wasm_bindgen(js_name = randomizerKinds)]
+    pub fn randomizer_kinds() -> Result<JsValue, JsValue> {
+        to_value(&randomizer_kinds()).map_err(|e| e.into())
+    }
+
+    #[wasm_bindgen(js_name = tick)]
+    pub fn tick(&mut self, dt_ms: f32) -> Result<JsValue, JsValue> {
+        self.tick_versus(dt_ms);
+        let view = self.versus.snapshot();
+        self.keep_events();
+        to_value(&view).map_err(|e| e.into())
+    }
+
+    /// Run `n` ticks of `dt_ms` with the current input and return only the final frame, whose
+    /// events cover all `n` ticks.
+    #[wasm_bindgen(js_name = stepFrames)]
+    pub fn step_frames(&mut self, n: u32, dt_ms: f32) -> Result<JsValue, JsValue> {
+        for _ in 0..n {
+            self.tick_versus(dt_ms);
+        }
+        let view = self.versus.snapshot();
+        self.keep_events();
+        to_value(&view).map_err(|e| e.into())
+    }
+
+    /// `tick`, but each player's field is replaced by `changed` cells after the first frame.
+    #[wasm_bindgen(js_name = tickDelta)]
+    pub fn tick_delta(&mut self, dt_ms: f32) -> Result<JsValue, JsValue> {
+        self.tick_versus(dt_ms);
+        let mut view = self.versus.snapshot();
+        self.keep_events();
+        for (idx, player) in view.players.iter_mut().enumerate() {
+            let Some(prev) = self.prev_fields.get_mut(idx) else {
+                self.prev_fields.push(player.field.clone());
+                continue;
+            };
+            let changed = player
+                .field
+                .iter()
+                .zip(prev.iter())
+                .enumerate()
+                .filter(|(_, (now, before))| now != before)
+                .map(|(i, (&now, _))| [i as u16, now as u16])
+                .collect();
+            *prev = std::mem::take(&mut player.field);
+            player.changed = Some(changed);
+        }
+        to_value(&view).map_err(|e| e.into())
+    }
+
+    /// `tick`, but returns `Versus::snapshot_packed` bytes as a `Uint8Array`. Events are not
+    /// included; collect them with `takeEvents`.
+    #[wasm_bindgen(js_name = tickPacked)]
+    pub fn tick_packed(&mut self, dt_ms: f32) -> Vec<u8> {
+        self.tick_versus(dt_ms);
+        self.versus.snapshot_packed()
+    }
+
+    /// Events raised since they were last returned, for callers using `tickPacked`.
+    #[wasm_bindgen(js_name = takeEvents)]
+    pub fn take_events(&mut self) -> Result<JsValue, JsValue> {
+        let events = self.take_frame_events();
+        to_value(&events).map_err(|e| e.into())
+    }
+
+    /// Every event since the last drain, across any number of ticks and whichever tick
+    /// variant ran them and whether `takeEvents` already returned them, oldest first; for
+    /// consumers (e.g. audio) on their own loop. Nothing is dropped between drains.
+    #[wasm_bindgen(js_name = drainEvents)]
+    pub fn drain_events(&mut self) -> Result<JsValue, JsValue> {
+        let events = self.drain_backlog();
+        to_value(&events).map_err(|e| e.into())
+    }
+
+    /// Both players' fields, stats and garbage meters for a spectator view; poll it at
+    /// whatever rate the overlay needs, independently of `tick`.
+    #[wasm_bindgen(js_name = broadcastSnapshot)]
+    pub fn broadcast_snapshot(&self) -> Result<JsValue, JsValue> {
+        to_value(&self.versus.broadcast_snapshot()).map_err(|e| e.into())
     }
 
     #[wasm_bindgen(js_name = setInput)]
     pub fn set_input(&mut self, input: JsValue) -> Result<(), JsValue> {
+        self.set_player_input(0, input)
+    }
+
+    /// Keyboard input for either player; only used while that player is human.
+    #[wasm_bindgen(js_name = setPlayerInput)]
+    pub fn set_player_input(&mut self, player: usize, input: JsValue) -> Result<(), JsValue> {
         let parsed: InputFrame = from_value(input)?;
-        self.input_state = InputState {
-            left: parsed.left,
-            right: parsed.right,
-            soft_drop: parsed.soft_drop,
-            hard_drop: parsed.hard_drop,
-            rotate_ccw: parsed.rotate_ccw,
-            rotate_cw: parsed.rotate_cw,
-            rotate_180: parsed.rotate_180,
-            hold: parsed.hold,
-            discard: parsed.discard,
-            force_i: parsed.force_i,
-        };
+        let state = self
+            .input_states
+            .get_mut(player)
+            .ok_or_else(|| JsValue::from_str("invalid player index"))?;
+        *state = parsed.into();
         Ok(())
     }
 
+    /// Hand a player to keyboard input (`setPlayerInput`) or back to the bot/TBP. Player 0
+    /// starts human and player 1 doesn't.
+    #[wasm_bindgen(js_name = setHumanPlayer)]
+    pub fn set_human_player(&mut self, player: usize, human: bool) -> Result<(), JsValue> {
+        self.versus
+            .set_human(player, human)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
     #[wasm_bindgen(js_name = setRandomizer)]
     pub fn set_randomizer(&mut self, player: usize, kind: JsValue) -> Result<(), JsValue> {
         let parsed: RandomizerKind = from_value(kind)?;
+        parsed.validate().map_err(|e| JsValue::from_str(&e))?;
+        parsed
+            .check_lookahead(self.versus.settings.lookahead_depth)
+            .map_err(|e| JsValue::from_str(&e))?;
         self.versus.set_randomizer(player, parsed);
         Ok(())
     }
 
+    /// Queue garbage for a player as if the opponent had attacked (for UI testing).
+    #[wasm_bindgen(js_name = injectGarbage)]
+    pub fn inject_garbage(&mut self, player: usize, lines: u32) -> Result<(), JsValue> {
+        self.versus
+            .inject_garbage(player, lines)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Have the internal bot speed up (or slow down) from `pps_start` to `pps_end` over
+    /// `ramp_seconds` of game time.
+    #[wasm_bindgen(js_name = setBotPpsRamp)]
+    pub fn set_bot_pps_ramp(&mut self, pps_start: f32, pps_end: f32, ramp_seconds: f32) {
+        let config = &mut self.versus.bot_driver.config;
+        config.pps_start = pps_start;
+        config.pps_end = pps_end;
+        config.ramp_seconds = ramp_seconds;
+    }
+
+    /// Have the internal bot tap one shift/rotate/drop at a time at its PPS pace rather
+    /// than sliding each piece over in one go.
+    #[wasm_bindgen(js_name = setBotSmooth)]
+    pub fn set_bot_smooth(&mut self, smooth: bool) {
+        self.versus.bot_driver.config.smooth = smooth;
+    }
+
+    /// `"LeftToRight"` (default) or `"CenterOut"`: which equally low column the internal bot
+    /// prefers.
+    #[wasm_bindgen(js_name = setBotTieBreak)]
+    pub fn set_bot_tie_break(&mut self, tie_break: JsValue) -> Result<(), JsValue> {
+        self.versus.bot_driver.config.tie_break = from_value(tie_break)?;
+        Ok(())
+    }
+
+    /// Schedule `[{ time_ms, lines }]` attacks against player 0, for solo downstack and
+    /// cancel drills. Leave the internal bot off so player 1 stays idle.
+    #[wasm_bindgen(js_name = loadGarbageScript)]
+    pub fn load_garbage_script(&mut self, script: JsValue) -> Result<(), JsValue> {
+        let attacks: Vec<ScriptedAttack> = from_value(script)?;
+        self.versus.load_garbage_script(attacks);
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = garbageScriptReport)]
+    pub fn garbage_script_report(&self) -> Result<JsValue, JsValue> {
+        to_value(&self.versus.garbage_script_report()).map_err(|e| e.into())
+    }
+
+    /// Freeze one player (e.g. the bot while practicing) while the other keeps playing.
+    #[wasm_bindgen(js_name = setPlayerPaused)]
+    pub fn set_player_paused(&mut self, player: usize, paused: bool) -> Result<(), JsValue> {
+        self.versus
+            .set_player_paused(player, paused)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Reject TBP moves whose given y collides instead of dropping them to the lowest fit,
+    /// so bot authors can check their coordinates exactly.
+    #[wasm_bindgen(js_name = setStrictTbp)]
+    pub fn set_strict_tbp(&mut self, strict: bool) {
+        self.versus.strict_tbp = strict;
+    }
+
+    /// Hold a player's inputs for `ticks` frames before applying them (QA / lag testing).
+    #[wasm_bindgen(js_name = setInputDelay)]
+    pub fn set_input_delay(&mut self, player: usize, ticks: usize) -> Result<(), JsValue> {
+        self.versus
+            .set_input_delay(player, ticks)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
     #[wasm_bindgen(js_name = setInternalBotEnabled)]
     pub fn set_internal_bot_enabled(&mut self, enabled: bool) {
         self.versus.use_internal_bot = enabled;
@@ -2346,6 +6757,226 @@ impl GameClient {
         to_value(&result).map_err(|e| e.into())
     }
 
+    /// Rows the player's piece fell last tick to gravity and to soft drop, plus both
+    /// accumulators; `null` unless the `fall_debug` setting is on.
+    #[wasm_bindgen(js_name = fallDebug)]
+    pub fn fall_debug(&self, player: usize) -> Result<JsValue, JsValue> {
+        let debug = self
+            .versus
+            .fall_debug
+            .get(player)
+            .ok_or_else(|| JsValue::from_str("invalid player index"))?;
+        let debug = self.versus.settings.fall_debug.then_some(debug);
+        to_value(&debug).map_err(|e| e.into())
+    }
+
+    /// Row indices (0 = bottom) containing garbage, e.g. to shade cheese or count what's left.
+    #[wasm_bindgen(js_name = garbageRows)]
+    pub fn garbage_rows(&self, player: usize) -> Result<Vec<usize>, JsValue> {
+        self.versus
+            .garbage_rows(player)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// `[{ x, y, rotation, lines }]` for each distinct spot the active piece can be hard
+    /// dropped to, for solvers and placement trainers.
+    #[wasm_bindgen(js_name = legalPlacements)]
+    pub fn legal_placements(&self, player: usize) -> Result<JsValue, JsValue> {
+        let placements = self
+            .versus
+            .legal_placements(player)
+            .map_err(|e| JsValue::from_str(&e))?;
+        to_value(&placements).map_err(|e| e.into())
+    }
+
+    /// Restore the player's board, pieces and bag to just after their last line clear.
+    #[wasm_bindgen(js_name = undoToLastClear)]
+    pub fn undo_to_last_clear(&mut self, player: usize) -> Result<(), JsValue> {
+        self.versus
+            .undo_to_last_clear(player)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Lock the active piece at its current height without dropping it first.
+    #[wasm_bindgen(js_name = forceLock)]
+    pub fn force_lock(&mut self, player: usize) -> Result<JsValue, JsValue> {
+        let result = self
+            .versus
+            .force_lock(player)
+            .map_err(|e| JsValue::from_str(&e))?;
+        to_value(&result).map_err(|e| e.into())
+    }
+
+    #[wasm_bindgen(js_name = bagState)]
+    pub fn bag_state(&self, player: usize) -> Result<Vec<u8>, JsValue> {
+        self.versus
+            .bag_state(player)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Debug: every cell of the active piece in engine coordinates (unmirrored, unrotated),
+    /// including any in the buffer at `y >= VISIBLE_HEIGHT`.
+    #[wasm_bindgen(js_name = activeCellsFull)]
+    pub fn active_cells_full(&self, player: usize) -> Result<JsValue, JsValue> {
+        let player = self
+            .versus
+            .players
+            .get(player)
+            .ok_or_else(|| JsValue::from_str("invalid player index"))?;
+        let active = &player.active;
+        let cells: Vec<Point> = active
+            .blocks()
+            .iter()
+            .map(|b| Point {
+                x: (active.x + b.x as i32) as i8,
+                y: (active.y + b.y as i32) as i8,
+            })
+            .collect();
+        to_value(&cells).map_err(|e| e.into())
+    }
+
+    /// Prefix of the upcoming pieces that can't change, for fading uncertain previews.
+    #[wasm_bindgen(js_name = guaranteedNext)]
+    pub fn guaranteed_next(&self, player: usize) -> Result<Vec<u8>, JsValue> {
+        self.versus
+            .guaranteed_next(player)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Gravity as milliseconds per row (default 1000).
+    #[wasm_bindgen(js_name = setGravityMs)]
+    pub fn set_gravity_ms(&mut self, ms_per_row: f32) -> Result<(), JsValue> {
+        self.versus
+            .set_gravity_ms(ms_per_row)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Gravity in G (rows per 60 fps frame), e.g. 1, 5 or 20.
+    #[wasm_bindgen(js_name = setGravityG)]
+    pub fn set_gravity_g(&mut self, g: f32) -> Result<(), JsValue> {
+        self.versus
+            .set_gravity_g(g)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Milliseconds until the active piece auto-locks; -1 while it is airborne.
+    #[wasm_bindgen(js_name = lockTimer)]
+    pub fn lock_timer(&self, player: usize) -> Result<f32, JsValue> {
+        self.versus
+            .lock_timer(player)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    #[wasm_bindgen(js_name = moveResetsRemaining)]
+    pub fn move_resets_remaining(&self, player: usize) -> Result<u8, JsValue> {
+        self.versus
+            .move_resets_remaining(player)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// Minimal key sequence to place the active piece where its ghost currently is.
+    #[wasm_bindgen(js_name = finesseHint)]
+    pub fn finesse_hint(&self, player: usize) -> Result<JsValue, JsValue> {
+        let hint = self
+            .versus
+            .finesse_hint(player)
+            .map_err(|e| JsValue::from_str(&e))?;
+        to_value(&hint).map_err(|e| e.into())
+    }
+
+    /// Load a puzzle field: `width * height` color ids, bottom row first.
+    #[wasm_bindgen(js_name = setField)]
+    pub fn set_field(
+        &mut self,
+        player: usize,
+        cells: Vec<u8>,
+        width: usize,
+        height: usize,
+        reset_active: bool,
+    ) -> Result<(), JsValue> {
+        self.versus
+            .set_field(player, &cells, width, height, reset_active)
+            .map_err(JsValue::from)
+    }
+
+    /// Replace a player's upcoming pieces (color ids 1-7) for puzzle setups; the randomizer
+    /// resumes once they run out.
+    #[wasm_bindgen(js_name = setQueue)]
+    pub fn set_queue(&mut self, player: usize, pieces: Vec<u8>, replace_active: bool) -> Result<(), JsValue> {
+        self.versus
+            .set_queue(player, &pieces, replace_active)
+            .map_err(JsValue::from)
+    }
+
+    /// Blocks of piece `piece` (color id) in `rotation` ("Spawn", "Right", "Reverse" or
+    /// "Left"), for previews drawn in a non-spawn orientation.
+    #[wasm_bindgen(js_name = previewBlocks)]
+    pub fn preview_blocks(&self, player: usize, piece: u8, rotation: JsValue) -> Result<JsValue, JsValue> {
+        let rotation: Rotation = from_value(rotation)?;
+        let blocks = self
+            .versus
+            .preview_blocks(player, piece, rotation)
+            .map_err(JsValue::from)?;
+        to_value(&blocks).map_err(|e| e.into())
+    }
+
+    #[wasm_bindgen(js_name = detailedStats)]
+    pub fn detailed_stats(&self, player: usize) -> Result<JsValue, JsValue> {
+        let stats = self
+            .versus
+            .detailed_stats(player)
+            .map_err(|e| JsValue::from_str(&e))?;
+        to_value(&stats).map_err(|e| e.into())
+    }
+
+    /// Post-game summary for both players, for the results screen.
+    #[wasm_bindgen(js_name = finalResults)]
+    pub fn final_results(&self) -> Result<JsValue, JsValue> {
+        to_value(&self.versus.final_results()).map_err(|e| e.into())
+    }
+
+    /// Check a TBP move against the current state without applying it.
+    #[wasm_bindgen(js_name = previewMove)]
+    pub fn preview_move(&self, player: usize, mv: JsValue) -> Result<JsValue, JsValue> {
+        let parsed: tbp_data::Move = from_value(mv)?;
+        let preview = self
+            .versus
+            .preview_tbp_move(player, &parsed)
+            .map_err(|e| JsValue::from_str(&e))?;
+        to_value(&preview).map_err(|e| e.into())
+    }
+
+    /// Apply a saved list of TBP moves in order (e.g. from a bot's game log) to find where a
+    /// replay diverges: returns each move's result, the index and reason of the first move
+    /// that fails, and the resulting board hash. Start from a fresh client, with `setQueue`
+    /// matching the log's pieces.
+    #[wasm_bindgen(js_name = replayMoves)]
+    pub fn replay_moves(&mut self, player: usize, moves: JsValue) -> Result<JsValue, JsValue> {
+        let parsed: Vec<tbp_data::Move> = from_value(moves)?;
+        let report = self
+            .versus
+            .replay_tbp_moves(player, parsed)
+            .map_err(|e| JsValue::from_str(&e))?;
+        to_value(&report).map_err(|e| e.into())
+    }
+
+    /// Kick offsets tried in order when rotating `piece` between two adjacent orientations.
+    #[wasm_bindgen(js_name = kicksFor)]
+    pub fn kicks_for(&self, piece: JsValue, from: JsValue, to: JsValue) -> Result<JsValue, JsValue> {
+        let piece: Tetromino = from_value(piece)?;
+        let from: Rotation = from_value(from)?;
+        let to: Rotation = from_value(to)?;
+        // 180s are performed as two 90-degree rotations, so there is no table for them.
+        if to != from.rotate_cw() && to != from.rotate_ccw() {
+            return Err(JsValue::from_str("rotations must be adjacent"));
+        }
+        let kicks: Vec<[i32; 2]> = KickTable::kicks(piece, from, to)
+            .into_iter()
+            .map(|(dx, dy)| [dx, dy])
+            .collect();
+        to_value(&kicks).map_err(|e| e.into())
+    }
+
     #[wasm_bindgen(js_name = tbpStartJson)]
     pub fn tbp_start_json(&self, player: usize) -> Result<String, JsValue> {
         let start = self
@@ -2354,26 +6985,61 @@ impl GameClient {
             .map_err(|e| JsValue::from_str(&e))?;
         serde_json::to_string(&start).map_err(|e| JsValue::from_str(&e.to_string()))
     }
-}
-fn detect_t_spin(board: &Board, active: &ActivePiece, last_rotation: bool, last_kick: (i32, i32)) -> bool {
-    if active.piece != Tetromino::T {
-        return false;
+
+    /// The settings in effect, after defaults filled in anything missing or malformed.
+    #[wasm_bindgen(js_name = settings)]
+    pub fn settings(&self) -> Result<JsValue, JsValue> {
+        to_value(&self.versus.settings).map_err(|e| e.into())
     }
-    if !last_rotation {
-        return false;
+
+    #[wasm_bindgen(js_name = dimensions)]
+    pub fn dimensions(&self) -> Result<JsValue, JsValue> {
+        to_value(&Dimensions {
+            width: WIDTH,
+            visible_height: VISIBLE_HEIGHT,
+            buffer_height: BUFFER_HEIGHT,
+        })
+        .map_err(|e| e.into())
+    }
+
+    /// TBP `stop` message (`{"type":"stop"}`): the bot should stop thinking about this game.
+    #[wasm_bindgen(js_name = tbpStop)]
+    pub fn tbp_stop(&self) -> Result<String, JsValue> {
+        tbp_message_json(frontend_msg::Stop::new().into())
+    }
+
+    /// TBP `quit` message (`{"type":"quit"}`): the bot should shut down.
+    #[wasm_bindgen(js_name = tbpQuit)]
+    pub fn tbp_quit(&self) -> Result<String, JsValue> {
+        tbp_message_json(frontend_msg::Quit::new().into())
     }
-    let cx = active.x;
-    let cy = active.y;
-    let corners = [
+}
+
+fn tbp_message_json(msg: frontend_msg::FrontendMessage) -> Result<String, JsValue> {
+    serde_json::to_string(&msg).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Occupancy of the four diagonal neighbours of the piece center: top-left, top-right,
+/// bottom-left, bottom-right.
+fn t_corners(board: &Board, active: &ActivePiece) -> [bool; 4] {
+    let (cx, cy) = (active.x, active.y);
+    [
         (cx - 1, cy + 1),
         (cx + 1, cy + 1),
         (cx - 1, cy - 1),
         (cx + 1, cy - 1),
-    ];
-    let mut occupied = [false; 4];
-    for (i, (x, y)) in corners.iter().enumerate() {
-        occupied[i] = board.is_occupied(*x, *y);
+    ]
+    .map(|(x, y)| board.is_occupied(x, y))
+}
+
+fn detect_t_spin(board: &Board, active: &ActivePiece, last_rotation: bool, last_kick: (i32, i32)) -> bool {
+    if active.piece != Tetromino::T || active.shape.is_some() {
+        return false;
+    }
+    if !last_rotation {
+        return false;
     }
+    let occupied = t_corners(board, active);
     let occupied_count = occupied.iter().filter(|v| **v).count();
     if occupied_count < 3 {
         return false;
@@ -2404,3 +7070,22 @@ fn detect_t_spin(board: &Board, active: &ActivePiece, last_rotation: bool, last_
     }
     true
 }
+
+/// The corner case `detect_t_spin` rejects: one front corner and both back corners filled
+/// after a rotation without a long kick.
+fn detect_t_spin_mini(board: &Board, active: &ActivePiece, last_rotation: bool, last_kick: (i32, i32)) -> bool {
+    if active.piece != Tetromino::T || active.shape.is_some() || !last_rotation {
+        return false;
+    }
+    let occupied = t_corners(board, active);
+    let (front, back) = match active.rotation {
+        Rotation::Spawn => ([0, 1], [2, 3]),
+        Rotation::Right => ([0, 2], [1, 3]),
+        Rotation::Reverse => ([2, 3], [0, 1]),
+        Rotation::Left => ([1, 3], [0, 2]),
+    };
+    let front_count = occupied[front[0]] as u8 + occupied[front[1]] as u8;
+    let back_count = occupied[back[0]] as u8 + occupied[back[1]] as u8;
+    let (dx, dy) = last_kick;
+    front_count == 1 && back_count == 2 && dx.abs() + dy.abs() < 3
+}
@@ -44,6 +44,7 @@ let gameEnded = false;
 let sentStopThisGame = false;
 let summaryLogs = [[], []];
 let lastStatsSnap = [null, null];
+let fieldCache = [];
 
 const actions = [
   { id: "left", label: "Move Left", field: "move_left" },
@@ -165,6 +166,10 @@ function handleBotMessage(raw) {
       setBotStatus("connecting", "Negotiating…");
       sendBot({ type: "rules" });
       break;
+    case "bot_info":
+      // Parsed by the bridge from the bot's TBP info.
+      console.info(`[tbp] bot ${msg.name} ${msg.version} by ${msg.author}`, msg.features);
+      break;
     case "ready":
       botReady = true;
       setBotStatus("connected");
@@ -174,6 +179,12 @@ function handleBotMessage(raw) {
       awaitingSuggestion = false;
       applyBotSuggestion(msg);
       break;
+    case "error":
+      // Sent by the bridge right before it closes (e.g. bad --bot-path).
+      console.error("Bot bridge error:", msg.message);
+      botReady = false;
+      setBotStatus("error", msg.message || "Bridge error");
+      break;
     default:
       break;
   }
@@ -188,7 +199,7 @@ function applyBotSuggestion(msg) {
       setBotStatus("error", "Bot topped out");
       gameEnded = true;
       sentStopThisGame = true;
-      sendBot({ type: "stop" });
+      sendBot(JSON.parse(game.tbpStop()));
       if (suggestTimer) {
         clearTimeout(suggestTimer);
         suggestTimer = null;
@@ -226,7 +237,9 @@ function connectBot() {
     handleBotMessage(ev.data);
   });
   botSocket.addEventListener("close", () => {
-    setBotStatus("disconnected");
+    if (!document.getElementById("botStatusDot")?.classList.contains("error")) {
+      setBotStatus("disconnected");
+    }
     botReady = false;
     if (suggestTimer) {
       clearTimeout(suggestTimer);
@@ -336,7 +349,8 @@ function createGameFromUI() {
     das: Number(document.getElementById("das").value || 133),
     arr: Number(document.getElementById("arr").value || 10),
     soft_drop: document.getElementById("softDrop").value,
-    ghost_enabled: document.getElementById("ghostToggle").value === "true",
+    ghost_enabled: document.getElementById("ghostToggle").value !== "false",
+    ghost_style: document.getElementById("ghostToggle").value === "outline" ? "Outline" : "Full",
     grid: document.getElementById("gridStyle").value,
   };
   const pps = Number(document.getElementById("pps").value || 1.8);
@@ -349,7 +363,7 @@ function createGameFromUI() {
     arr: settings.arr,
     softDrop: settings.soft_drop,
     gridStyle: settings.grid,
-    ghost: settings.ghost_enabled,
+    ghost: document.getElementById("ghostToggle").value,
     pps,
     previewCount,
     randomizers: {
@@ -367,6 +381,7 @@ function createGameFromUI() {
     suggestTimer = null;
   }
   game = new GameClient(settings, pps, randomizers);
+  fieldCache = [];
   window.tbpSnapshot = () => game.tbpStart(1);
   botPendingStart = true;
   sendBotStart();
@@ -378,7 +393,8 @@ function restoreSettings() {
   document.getElementById("arr").value = saved.arr;
   document.getElementById("softDrop").value = saved.softDrop;
   document.getElementById("gridStyle").value = saved.gridStyle;
-  document.getElementById("ghostToggle").value = saved.ghost ? "true" : "false";
+  document.getElementById("ghostToggle").value =
+    saved.ghost === "outline" ? "outline" : saved.ghost === true || saved.ghost === "true" ? "true" : "false";
   document.getElementById("pps").value = saved.pps;
   document.getElementById("ppsValue").textContent = `${saved.pps.toFixed(1)} PPS`;
   document.getElementById("randPlayer").value = saved.randomizers.player.kind;
@@ -476,6 +492,12 @@ function drawBoard(canvas, player, gridStyle, gameOver = false, isWinner = false
   }
   const activeColor = COLORS[player.active_color || 7] || "rgba(255,255,255,0.35)";
   drawBlocks(player.active, activeColor);
+  if (player.lock_progress > 0) {
+    // Brighten the piece as its lock delay runs out.
+    ctx.globalAlpha = 0.5 * player.lock_progress;
+    drawBlocks(player.active, "#ffffff");
+    ctx.globalAlpha = 1.0;
+  }
 
   // Incoming garbage bar (Full blocking style).
   if (pendingGarbage > 0) {
@@ -523,6 +545,8 @@ function drawHold(canvas, player) {
   // Prevent overflow if the hold canvas is smaller.
   const cell = Math.min(baseCell, canvas.width / 5, canvas.height / 5);
   ctx.fillStyle = COLORS[colorId];
+  // Gray out the held piece while hold is spent for this turn.
+  ctx.globalAlpha = player.hold_available === false ? 0.35 : 1;
   const minX = Math.min(...shapes.map((p) => p.x));
   const maxX = Math.max(...shapes.map((p) => p.x));
   const minY = Math.min(...shapes.map((p) => p.y));
@@ -536,6 +560,7 @@ function drawHold(canvas, player) {
     const drawY = originY - p.y * cell;
     ctx.fillRect(drawX, drawY, cell - 1, cell - 1);
   });
+  ctx.globalAlpha = 1;
 }
 
 function drawNext(canvas, player, count) {
@@ -566,6 +591,22 @@ function drawNext(canvas, player, count) {
   }
 }
 
+// tickDelta sends the full field once, then only changed cells; rebuild player.field from the cache.
+function applyFieldDelta(view) {
+  if (!view || !view.players) return view;
+  view.players.forEach((player, i) => {
+    if (player.field) {
+      fieldCache[i] = player.field.slice();
+    } else if (fieldCache[i]) {
+      for (const [idx, colorId] of player.changed || []) {
+        fieldCache[i][idx] = colorId;
+      }
+    }
+    player.field = fieldCache[i] || [];
+  });
+  return view;
+}
+
 function formatNumber(num, digits = 2) {
   if (!isFinite(num)) return "0.00";
   return num.toFixed(digits);
@@ -583,12 +624,14 @@ function updateStats(players, dt) {
     const ppsEl = document.getElementById(`${prefix}-pps`);
     const kppEl = document.getElementById(`${prefix}-kpp`);
     const linesEl = document.getElementById(`${prefix}-lines`);
+    const cancelledEl = document.getElementById(`${prefix}-cancelled`);
     if (timeEl) timeEl.textContent = `${formatNumber(timeSec, 1)}s`;
     if (attackEl) attackEl.textContent = `${stats.attack ?? 0}`;
     if (finesseEl) finesseEl.textContent = `${stats.finesse ?? 0}`;
     if (ppsEl) ppsEl.textContent = formatNumber(stats.pps || 0, 2);
     if (kppEl) kppEl.textContent = formatNumber(stats.kpp || 0, 2);
     if (linesEl) linesEl.textContent = `${stats.lines_sent ?? 0}`;
+    if (cancelledEl) cancelledEl.textContent = `${stats.garbage_cancelled ?? 0}`;
   }
 }
 
@@ -662,8 +705,8 @@ async function main() {
       // Ensure all fields are present for WASM deserialization.
       const sendState = { force_i: false, ...inputState };
       game.setInput(sendState);
-      const frame = game.tick(dt);
-      const view = frame;
+      const frame = game.tickDelta(dt);
+      const view = applyFieldDelta(frame);
       if (view && view.players) {
         window.lastView = view;
         const gameOver = view.players.some((p) => p.topped_out);
@@ -672,7 +715,7 @@ async function main() {
         if (gameOver && !gameEnded) {
           gameEnded = true;
           if (!sentStopThisGame) {
-            sendBot({ type: "stop" });
+            sendBot(JSON.parse(game.tbpStop()));
             sentStopThisGame = true;
           }
           if (suggestTimer) {